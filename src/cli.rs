@@ -0,0 +1,348 @@
+//! The command line interface, which runs the same checks and formatting as the editor.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    diagnostics::{self, Diagnostic},
+    formatter::{self, FormatOptions},
+    line_index::{LineIndex, PositionEncoding},
+    logging::LogConfig,
+    parser,
+    schema::{self, SchemaConfig},
+};
+
+pub const USAGE: &str = "\
+Usage: toml-lsp <command> [options]
+
+Commands:
+  check <files>...          Report the errors in the files, and against the schema that a
+                            `#:schema ./path.json` comment in them names
+  fmt [--check] <files>...  Format the files in place, or only report which ones would change
+  parse [--tree] <file>     Print the syntax errors of a file, and with --tree its syntax tree
+  lsp                       Run the language server over stdio
+  help                      Print this message
+
+Options of lsp:
+  --log-level <filter>      What to log, like `info` or `warn,toml_lsp::parser=trace`
+  --log-file <path>         The file to append the log to
+  --log-stderr              Log to stderr as well
+
+The options of lsp can also be set with the environment variables TOML_LSP_LOG_LEVEL,
+TOML_LSP_LOG_FILE and TOML_LSP_LOG_STDERR, or in the `log` object of the
+initializationOptions. Options win over environment variables, which win over the
+initializationOptions.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Check { files: Vec<PathBuf> },
+    Fmt { check: bool, files: Vec<PathBuf> },
+    Parse { tree: bool, file: PathBuf },
+    Lsp { log: LogConfig },
+    Help,
+}
+
+/// The options that take a value, either as the next argument or after a `=`.
+const VALUE_FLAGS: &[&str] = &["--log-level", "--log-file"];
+
+impl Command {
+    /// Parses the arguments after the name of the binary.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+        let mut args = args.into_iter();
+        let command = args.next().ok_or("No command given")?;
+
+        let mut flags = Vec::new();
+        let mut files = Vec::new();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                files.push(PathBuf::from(arg));
+                continue;
+            }
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None if VALUE_FLAGS.contains(&&*arg) => {
+                    let value = args.next().ok_or(format!("`{}` needs a value", arg))?;
+                    (arg, Some(value))
+                }
+                None => (arg, None),
+            };
+            flags.push((name, value));
+        }
+        let mut flag = |name: &str| {
+            let found = flags.iter().position(|(flag, _)| flag == name);
+            found.map(|i| flags.remove(i))
+        };
+
+        let needs_files = matches!(&*command, "check" | "fmt");
+        if needs_files && files.is_empty() {
+            return Err("No files given".to_string());
+        } else if matches!(&*command, "lsp" | "help") && !files.is_empty() {
+            return Err(format!("Unexpected argument `{}`", files[0].display()));
+        }
+
+        let command = match &*command {
+            "check" => Command::Check { files },
+            "fmt" => Command::Fmt {
+                check: flag("--check").is_some(),
+                files,
+            },
+            "parse" => {
+                let tree = flag("--tree").is_some();
+                if files.len() != 1 {
+                    return Err("`parse` takes exactly one file".to_string());
+                }
+                Command::Parse {
+                    tree,
+                    file: files.remove(0),
+                }
+            }
+            "lsp" => Command::Lsp {
+                log: LogConfig {
+                    level: flag("--log-level").and_then(|(_, value)| value),
+                    file: flag("--log-file").and_then(|(_, value)| value.map(PathBuf::from)),
+                    stderr: flag("--log-stderr").map(|_| true),
+                },
+            },
+            "help" | "--help" | "-h" => Command::Help,
+            command => return Err(format!("Unknown command `{}`", command)),
+        };
+
+        match flags.first() {
+            Some((flag, _)) => Err(format!("Unknown option `{}`", flag)),
+            None => Ok(command),
+        }
+    }
+}
+
+/// Runs a command other than `lsp`, and returns the exit code: 0 on success, 1 if a file
+/// has errors or is not formatted, and 2 if a file could not be read or written.
+pub fn run(command: Command) -> i32 {
+    match command {
+        Command::Check { files } => check(&files),
+        Command::Fmt { check, files } => fmt(check, &files),
+        Command::Parse { tree, file } => parse(tree, &file),
+        Command::Lsp { .. } => unreachable!("the language server does not run as a command"),
+        Command::Help => {
+            println!("{}", USAGE);
+            0
+        }
+    }
+}
+
+fn check(files: &[PathBuf]) -> i32 {
+    let mut schemas = schema::Store::default();
+    let mut code = 0;
+    for path in files {
+        let text = match read(path) {
+            Some(text) => text,
+            None => {
+                code = 2;
+                continue;
+            }
+        };
+        let ast = parser::parse(&text);
+        let mut diagnostics = diagnostics::diagnostics(&ast);
+        if let Some(source) = schema::locate(&ast, path, None, &SchemaConfig::default()) {
+            diagnostics.extend(diagnostics::schema_diagnostics(&ast, &source, &mut schemas));
+        }
+        diagnostics.sort_by_key(|diagnostic| diagnostic.range.start());
+        let line_index = LineIndex::new(&text, PositionEncoding::Utf32);
+        let path = path.display().to_string();
+        for diagnostic in &diagnostics {
+            print!("{}", render(&path, &text, &line_index, diagnostic));
+        }
+        if !diagnostics.is_empty() {
+            code = code.max(1);
+        }
+    }
+    code
+}
+
+fn fmt(check: bool, files: &[PathBuf]) -> i32 {
+    let options = FormatOptions::default();
+    let mut code = 0;
+    for path in files {
+        let text = match read(path) {
+            Some(text) => text,
+            None => {
+                code = 2;
+                continue;
+            }
+        };
+        let formatted = match formatter::format(&parser::parse(&text), &options) {
+            Some(formatted) => formatted,
+            None => {
+                eprintln!(
+                    "error: {} has syntax errors and cannot be formatted",
+                    path.display()
+                );
+                code = code.max(1);
+                continue;
+            }
+        };
+        if formatted == text {
+            continue;
+        }
+        if check {
+            println!("Would reformat {}", path.display());
+            code = code.max(1);
+        } else if let Err(err) = fs::write(path, formatted) {
+            eprintln!("error: failed to write {}: {}", path.display(), err);
+            code = 2;
+        }
+    }
+    code
+}
+
+fn parse(tree: bool, path: &Path) -> i32 {
+    let text = match read(path) {
+        Some(text) => text,
+        None => return 2,
+    };
+    let ast = parser::parse(&text);
+    for error in ast.errors() {
+        println!("error[{}] @{:?}: {}", error.code(), error.range(), error);
+    }
+    if tree {
+        println!("{}", ast.debug());
+    }
+    if ast.errors().is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+fn read(path: &Path) -> Option<String> {
+    match fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(err) => {
+            eprintln!("error: failed to read {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Renders a diagnostic with the line it is on, marking its range up to the end of that line.
+/// The line index has to count characters in UTF-32, as the columns are shown.
+fn render(path: &str, text: &str, line_index: &LineIndex, diagnostic: &Diagnostic) -> String {
+    let location = |range: rowan::TextRange| {
+        let position = line_index.position(range.start());
+        format!("{}:{}:{}", path, position.line + 1, position.character + 1)
+    };
+
+    let start = line_index.position(diagnostic.range.start());
+    let end = line_index.position(diagnostic.range.end());
+    let line = &text[line_index.line(start.line)];
+    let end_character = if end.line == start.line {
+        end.character
+    } else {
+        line.chars().count() as u32
+    };
+    let number = (start.line + 1).to_string();
+    let gutter = " ".repeat(number.len());
+    // Tabs are kept so that the marker lines up however wide they are shown.
+    let padding: String = line
+        .chars()
+        .take(start.character as usize)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let marker = "^".repeat(end_character.saturating_sub(start.character).max(1) as usize);
+
+    let mut out = String::new();
+    writeln!(out, "error[{}]: {}", diagnostic.code, diagnostic.message).unwrap();
+    writeln!(out, "{}--> {}", gutter, location(diagnostic.range)).unwrap();
+    writeln!(out, "{} |", gutter).unwrap();
+    writeln!(out, "{} | {}", number, line).unwrap();
+    writeln!(out, "{} | {}{}", gutter, padding, marker).unwrap();
+    if let Some(first) = diagnostic.first_definition {
+        writeln!(
+            out,
+            "{} = note: first defined at {}",
+            gutter,
+            location(first)
+        )
+        .unwrap();
+    }
+    writeln!(out).unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+
+    fn args(args: &str) -> Result<Command, String> {
+        Command::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(
+            args("fmt --check a.toml b.toml"),
+            Ok(Command::Fmt {
+                check: true,
+                files: vec!["a.toml".into(), "b.toml".into()],
+            })
+        );
+        assert_eq!(
+            args("parse a.toml --tree"),
+            Ok(Command::Parse {
+                tree: true,
+                file: "a.toml".into(),
+            })
+        );
+        assert_eq!(
+            args("lsp --log-level debug --log-file=a.log --log-stderr"),
+            Ok(Command::Lsp {
+                log: LogConfig {
+                    level: Some("debug".into()),
+                    file: Some("a.log".into()),
+                    stderr: Some(true),
+                },
+            })
+        );
+        assert_eq!(
+            args("lsp --log-level"),
+            Err("`--log-level` needs a value".to_string())
+        );
+        assert_eq!(args(""), Err("No command given".to_string()));
+        assert_eq!(args("check"), Err("No files given".to_string()));
+        assert_eq!(
+            args("check --tree a.toml"),
+            Err("Unknown option `--tree`".to_string())
+        );
+        assert_eq!(args("serve"), Err("Unknown command `serve`".to_string()));
+    }
+
+    #[test]
+    fn snippets() {
+        let text = "a = 1\n\tb = [1,,]\na = \"é\"\n";
+        let line_index = LineIndex::new(text, PositionEncoding::Utf32);
+        let out: String = diagnostics::diagnostics(&parser::parse(text))
+            .iter()
+            .map(|diagnostic| render("test.toml", text, &line_index, diagnostic))
+            .collect();
+        expect![[r#"
+            error[expected-value]: Expected a value
+             --> test.toml:2:9
+              |
+            2 | 	b = [1,,]
+              | 	       ^
+
+            error[duplicate-key]: Duplicate key `a`
+             --> test.toml:3:1
+              |
+            3 | a = "é"
+              | ^
+              = note: first defined at test.toml:1:1
+
+        "#]]
+        .assert_eq(&out);
+    }
+}
@@ -0,0 +1,440 @@
+//! Keys and values that the schema of a document allows, for `textDocument/completion`.
+//!
+//! What is being typed is usually not valid TOML yet, so the context of the cursor is read
+//! from the text of its line, and only the sections before it come from the syntax tree.
+
+use lsp_types::{
+    CompletionItem, CompletionItemKind, Documentation, InsertTextFormat, MarkupContent,
+    MarkupKind,
+};
+use rowan::TextSize;
+use serde_json::Value as Json;
+
+use crate::{
+    parser::{ast, AST},
+    schema::{self, Schema},
+    semantic::{self, ArrayKind, Path, PathSegment, ValueKind},
+};
+
+pub fn completions(
+    ast: &AST,
+    document: &semantic::Document,
+    schema: &Schema,
+    offset: TextSize,
+) -> Vec<CompletionItem> {
+    let text = ast.node().text().to_string();
+    let offset = usize::from(offset).min(text.len());
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[line_start..offset];
+
+    let header = line.trim_start();
+    if header.starts_with('[') {
+        let array = header.starts_with("[[");
+        let typed = header.trim_start_matches('[');
+        if typed.contains(']') {
+            return Vec::new();
+        }
+        let (parents, _) = split_key(typed);
+        let path = document
+            .root
+            .resolve_header(&parents, TextSize::from(line_start as u32));
+        return header_keys(document, schema, &path, array);
+    }
+
+    let mut path = section(ast, document, TextSize::from(line_start as u32));
+    let segment = match inline_tables(line, &mut path) {
+        Some(segment) => segment,
+        None => return Vec::new(),
+    };
+    match segment.split_once('=') {
+        Some((key, _)) => {
+            let (mut names, last) = split_key(key);
+            names.push(last);
+            path.0.extend(names.into_iter().map(PathSegment::Key));
+            values(schema, &path)
+        }
+        None => {
+            let (parents, _) = split_key(segment);
+            path.0.extend(parents.into_iter().map(PathSegment::Key));
+            keys(document, schema, &path)
+        }
+    }
+}
+
+/// The path of the table that the section around `offset` defines.
+fn section(ast: &AST, document: &semantic::Document, offset: TextSize) -> Path {
+    let header = ast
+        .root()
+        .entries()
+        .take_while(|entry| entry.syntax().text_range().start() <= offset)
+        .filter_map(|entry| {
+            let key = match &entry {
+                ast::Entry::KeyValue(_) => return None,
+                ast::Entry::Table(header) => header.key()?,
+                ast::Entry::ArrayOfTables(header) => header.key()?,
+            };
+            let names: Option<Vec<_>> = key.segments().map(|segment| segment.name()).collect();
+            Some((names?, entry.syntax().text_range().start()))
+        })
+        .last();
+    match header {
+        Some((names, start)) => document.root.resolve_header(&names, start),
+        None => Path::default(),
+    }
+}
+
+/// Follows the inline tables that are still open at the end of `line`, adding their keys to
+/// `path`, and returns what comes after the last `{` or `,`. Returns `None` in comments,
+/// and in strings and arrays other than the value being typed.
+fn inline_tables<'a>(line: &'a str, path: &mut Path) -> Option<&'a str> {
+    let mut open = Vec::new();
+    let mut segment_start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut brackets = 0;
+    for (i, c) in line.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' && q == '"' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '#' => return None,
+            '[' => brackets += 1,
+            ']' => brackets -= 1,
+            '{' if brackets == 0 => {
+                let (key, _) = line[segment_start..i].split_once('=')?;
+                let (mut names, last) = split_key(key);
+                names.push(last);
+                open.push(names.len());
+                path.0.extend(names.into_iter().map(PathSegment::Key));
+                segment_start = i + 1;
+            }
+            '}' if brackets == 0 => {
+                let len = open.pop()?;
+                path.0.truncate(path.0.len() - len);
+                segment_start = i + 1;
+            }
+            ',' if brackets == 0 && !open.is_empty() => segment_start = i + 1,
+            _ => (),
+        }
+    }
+    let segment = &line[segment_start..];
+    let in_value = segment.contains('=');
+    if (quote.is_some() || brackets > 0) && !in_value {
+        return None;
+    }
+    Some(segment)
+}
+
+/// Splits a dotted key that is being typed into the names before the last `.`, and the
+/// part after it.
+fn split_key(key: &str) -> (Vec<String>, String) {
+    let mut names = Vec::new();
+    let mut name = String::new();
+    let mut quote = None;
+    for c in key.chars() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '.') => names.push(std::mem::take(&mut name).trim().to_string()),
+            (None, c) if c.is_whitespace() => (),
+            _ => name.push(c),
+        }
+    }
+    (names, name)
+}
+
+/// The keys of the table at `path` that the schema allows, except the ones it has already.
+fn keys(document: &semantic::Document, schema: &Schema, path: &Path) -> Vec<CompletionItem> {
+    let table = table_at(document, path);
+    schema::properties(&schema.resolve(&path.0))
+        .into_iter()
+        .filter(|(key, _)| table.is_none_or(|table| table.get(key).is_none()))
+        .map(|(key, property)| {
+            let schemas = schema.expanded(property);
+            let key = key_text(key);
+            CompletionItem {
+                insert_text: Some(format!(
+                    "{} = {}",
+                    escape_snippet(&key),
+                    placeholder(&schemas)
+                )),
+                insert_text_format: Some(InsertTextFormat::Snippet),
+                ..key_item(key, &schemas)
+            }
+        })
+        .collect()
+}
+
+/// The keys that a `[table]` or, if `array`, an `[[array]]` header can add to the table at
+/// `path`.
+fn header_keys(
+    document: &semantic::Document,
+    schema: &Schema,
+    path: &Path,
+    array: bool,
+) -> Vec<CompletionItem> {
+    let table = table_at(document, path);
+    schema::properties(&schema.resolve(&path.0))
+        .into_iter()
+        .filter(|(key, _)| {
+            let existing = table.and_then(|table| table.get(key));
+            match existing.map(|entry| &entry.value.kind) {
+                None => true,
+                Some(ValueKind::Array(array_of_tables)) => {
+                    array && array_of_tables.kind == ArrayKind::OfTables
+                }
+                Some(_) => false,
+            }
+        })
+        .filter_map(|(key, property)| {
+            let schemas = schema.expanded(property);
+            let types: Vec<_> = schemas.iter().flat_map(|it| schema::types(it)).collect();
+            let wanted = if array { "array" } else { "object" };
+            if !types.is_empty() && !types.contains(&wanted) {
+                return None;
+            }
+            Some(key_item(key_text(key), &schemas))
+        })
+        .collect()
+}
+
+/// The members of `enum`s and `const`s, and `true` and `false` for booleans.
+fn values(schema: &Schema, path: &Path) -> Vec<CompletionItem> {
+    let mut items: Vec<CompletionItem> = Vec::new();
+    let mut push = |label: String, kind, schema: &Json| {
+        if items.iter().all(|item| item.label != label) {
+            items.push(CompletionItem {
+                label,
+                kind: Some(kind),
+                documentation: documentation(&[schema]),
+                ..CompletionItem::default()
+            });
+        }
+    };
+    for value_schema in schema.resolve(&path.0) {
+        let members = value_schema.get("enum").and_then(Json::as_array);
+        let constant = value_schema.get("const");
+        for member in members.into_iter().flatten().chain(constant) {
+            if let Some(literal) = schema::literal(member) {
+                push(literal, CompletionItemKind::EnumMember, value_schema);
+            }
+        }
+        if schema::types(value_schema).contains(&"boolean") {
+            for literal in &["true", "false"] {
+                push(literal.to_string(), CompletionItemKind::Value, value_schema);
+            }
+        }
+    }
+    items
+}
+
+fn table_at<'a>(document: &'a semantic::Document, path: &Path) -> Option<&'a semantic::Table> {
+    if path.0.is_empty() {
+        return Some(&document.root);
+    }
+    match &document.root.lookup(&path.0)?.kind {
+        ValueKind::Table(table) => Some(table),
+        _ => None,
+    }
+}
+
+fn key_item(label: String, schemas: &[&Json]) -> CompletionItem {
+    let types: Vec<_> = schemas.iter().flat_map(|it| schema::types(it)).collect();
+    CompletionItem {
+        label,
+        kind: Some(CompletionItemKind::Property),
+        detail: Some(types.join(" | ")).filter(|detail| !detail.is_empty()),
+        documentation: documentation(schemas),
+        ..CompletionItem::default()
+    }
+}
+
+/// The first `description` of `schemas`, or else their first `title`.
+fn documentation(schemas: &[&Json]) -> Option<Documentation> {
+    let text = |name| {
+        schemas
+            .iter()
+            .find_map(|schema| schema.get(name).and_then(Json::as_str))
+    };
+    let value = text("description").or_else(|| text("title"))?;
+    Some(Documentation::MarkupContent(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: value.to_string(),
+    }))
+}
+
+/// A key as it is written, with quotes if it is not a bare key.
+fn key_text(key: &str) -> String {
+    Path(vec![PathSegment::Key(key.to_string())]).to_string()
+}
+
+/// The snippet of a value of the schema: a choice of its `enum`, or an empty value of its
+/// type.
+fn placeholder(schemas: &[&Json]) -> String {
+    let members: Vec<_> = schemas
+        .iter()
+        .filter_map(|schema| schema.get("enum").and_then(Json::as_array))
+        .flatten()
+        .filter_map(schema::literal)
+        .map(|member| escape_choice(&member))
+        .collect();
+    if !members.is_empty() {
+        return format!("${{1|{}|}}", members.join(","));
+    }
+    let types: Vec<_> = schemas.iter().flat_map(|it| schema::types(it)).collect();
+    match types.first() {
+        Some(&"string") => "\"$1\"".to_string(),
+        Some(&"integer") | Some(&"number") => "${1:0}".to_string(),
+        Some(&"boolean") => "${1|true,false|}".to_string(),
+        Some(&"array") => "[$1]".to_string(),
+        Some(&"object") => "{ $1 }".to_string(),
+        _ => "$1".to_string(),
+    }
+}
+
+fn escape_snippet(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('$', "\\$")
+        .replace('}', "\\}")
+}
+
+fn escape_choice(text: &str) -> String {
+    escape_snippet(text).replace(',', "\\,").replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::{expect, Expect};
+
+    use super::*;
+    use crate::parser::parse;
+
+    const SCHEMA: &str = r##"{
+        "properties": {
+            "package": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "The name of the package." },
+                    "version": { "type": "string" },
+                    "edition": { "enum": ["2015", "2018"] },
+                    "publish": { "type": "boolean" }
+                }
+            },
+            "dependencies": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/definitions/dependency" }
+            },
+            "bin": {
+                "type": "array",
+                "items": { "properties": { "name": { "type": "string" } } }
+            }
+        },
+        "definitions": {
+            "dependency": {
+                "oneOf": [
+                    { "type": "string" },
+                    {
+                        "type": "object",
+                        "properties": {
+                            "version": { "type": "string" },
+                            "optional": { "type": "boolean", "default": false }
+                        }
+                    }
+                ]
+            }
+        }
+    }"##;
+
+    /// Completes at the `$0` in `input`.
+    fn check(input: &str, expect: Expect) {
+        let offset = input.find("$0").expect("no cursor in input");
+        let input = input.replace("$0", "");
+        let ast = parse(&input);
+        let document = semantic::lower(&ast.root());
+        let schema = Schema::new(serde_json::from_str(SCHEMA).unwrap());
+        let out: String = completions(&ast, &document, &schema, (offset as u32).into())
+            .into_iter()
+            .map(|item| {
+                let mut line = item.label.clone();
+                if let Some(insert) = item.insert_text {
+                    line.push_str(&format!(" => {}", insert));
+                }
+                if let Some(Documentation::MarkupContent(docs)) = item.documentation {
+                    line.push_str(&format!(" ({})", docs.value));
+                }
+                line + "\n"
+            })
+            .collect();
+        expect.assert_eq(&out);
+    }
+
+    #[test]
+    fn keys() {
+        check(
+            "[package]\nname = \"a\"\n$0\n",
+            expect![[r#"
+                edition => edition = ${1|"2015","2018"|}
+                publish => publish = ${1|true,false|}
+                version => version = "$1"
+            "#]],
+        );
+        check(
+            "[dependencies]\nserde = { version = \"1\", $0 }\n",
+            expect![[r#"
+                optional => optional = ${1|true,false|}
+            "#]],
+        );
+        check(
+            "package.$0\n",
+            expect![[r#"
+                edition => edition = ${1|"2015","2018"|}
+                name => name = "$1" (The name of the package.)
+                publish => publish = ${1|true,false|}
+                version => version = "$1"
+            "#]],
+        );
+        check("# $0\n", expect![[""]]);
+    }
+
+    #[test]
+    fn headers() {
+        check(
+            "[package]\n[$0",
+            expect![[r#"
+                dependencies
+            "#]],
+        );
+        check(
+            "[[bin]]\n[[$0",
+            expect![[r#"
+                bin
+            "#]],
+        );
+    }
+
+    #[test]
+    fn values() {
+        check(
+            "[package]\nedition = $0\n",
+            expect![[r#"
+                "2015"
+                "2018"
+            "#]],
+        );
+        check(
+            "[dependencies]\nserde = { optional = $0 }\n",
+            expect![[r#"
+                true
+                false
+            "#]],
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! Everything that is wrong with a document, as reported to editors and on the command line.
+
+use rowan::TextRange;
+
+use crate::{
+    parser::AST,
+    schema::{self, Source},
+    semantic,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// A stable identifier for the kind of problem.
+    pub code: &'static str,
+    pub message: String,
+    pub range: TextRange,
+    /// The earlier definition, for keys and tables that are defined twice.
+    pub first_definition: Option<TextRange>,
+}
+
+/// The syntax errors of a document, followed by the definitions that break TOML's rules.
+pub fn diagnostics(ast: &AST) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<_> = ast
+        .errors()
+        .into_iter()
+        .map(|err| Diagnostic {
+            code: err.code(),
+            message: err.to_string(),
+            range: err.range(),
+            first_definition: None,
+        })
+        .collect();
+    diagnostics.extend(
+        semantic::lower(&ast.root())
+            .errors
+            .into_iter()
+            .map(|err| Diagnostic {
+                code: err.code(),
+                message: err.to_string(),
+                range: err.range(),
+                first_definition: Some(err.first_definition()),
+            }),
+    );
+    diagnostics
+}
+
+/// The values of a document that its schema does not allow, or why the schema could not be
+/// loaded, which is reported at the `#:schema` comment if it names the schema.
+pub fn schema_diagnostics(
+    ast: &AST,
+    source: &Source,
+    store: &mut schema::Store,
+) -> Vec<Diagnostic> {
+    let schema = match store.get(&source.path) {
+        Ok(schema) => schema,
+        Err(message) => {
+            return vec![Diagnostic {
+                code: "schema-load",
+                message,
+                range: source.directive.unwrap_or_default(),
+                first_definition: None,
+            }]
+        }
+    };
+    schema
+        .validate(&semantic::lower(&ast.root()).root)
+        .into_iter()
+        .map(|err| Diagnostic {
+            code: err.code(),
+            message: err.to_string(),
+            range: err.range(),
+            first_definition: None,
+        })
+        .collect()
+}
@@ -0,0 +1,138 @@
+use lsp_types::TextDocumentContentChangeEvent;
+use ropey::Rope;
+
+use crate::{
+    line_index::{LineIndex, PositionEncoding},
+    parser::{self, TextEdit, AST},
+};
+
+/// An open document, as last synchronized with the client.
+#[derive(Debug, Clone)]
+pub struct Document {
+    text: Rope,
+    version: i32,
+    ast: AST,
+    line_index: LineIndex,
+}
+
+impl Document {
+    pub fn new(text: &str, version: i32, encoding: PositionEncoding) -> Document {
+        Document {
+            text: Rope::from_str(text),
+            version,
+            ast: parser::parse(text),
+            line_index: LineIndex::new(text, encoding),
+        }
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    #[cfg(test)]
+    pub fn text(&self) -> String {
+        self.text.to_string()
+    }
+
+    pub fn ast(&self) -> &AST {
+        &self.ast
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        &self.line_index
+    }
+
+    /// Applies the changes of a `textDocument/didChange` notification in order, reparsing
+    /// only what each of them touched. Changes without a range replace the whole text.
+    pub fn apply_changes(&mut self, changes: &[TextDocumentContentChangeEvent], version: i32) {
+        let encoding = self.line_index.encoding();
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    let delete = self.line_index.text_range(range);
+                    let start = self.text.byte_to_char(delete.start().into());
+                    let end = self.text.byte_to_char(delete.end().into());
+                    self.text.remove(start..end);
+                    self.text.insert(start, &change.text);
+
+                    let edit = TextEdit {
+                        delete,
+                        insert: change.text.clone(),
+                    };
+                    self.ast = parser::reparse(&self.ast, &edit, &self.text);
+                }
+                None => {
+                    self.text = Rope::from_str(&change.text);
+                    self.ast = parser::parse(&change.text);
+                }
+            }
+            // Later changes are relative to the text with this one applied.
+            self.line_index = LineIndex::from_chunks(self.text.chunks(), encoding);
+        }
+        self.version = version;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, Range};
+
+    use super::*;
+
+    fn change(
+        range: Option<((u32, u32), (u32, u32))>,
+        text: &str,
+    ) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: range.map(|((l1, c1), (l2, c2))| {
+                Range::new(Position::new(l1, c1), Position::new(l2, c2))
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    fn open(text: &str) -> Document {
+        Document::new(text, 1, PositionEncoding::Utf16)
+    }
+
+    #[test]
+    fn ranged_changes() {
+        let mut document = open("a = 1\nb = 2\n");
+        document.apply_changes(
+            &[
+                change(Some(((0, 4), (0, 5))), "10"),
+                change(Some(((1, 0), (1, 0))), "c = 3\n"),
+            ],
+            2,
+        );
+        assert_eq!(document.text(), "a = 10\nc = 3\nb = 2\n");
+        assert_eq!(document.version(), 2);
+        assert!(document.ast().errors().is_empty());
+    }
+
+    #[test]
+    fn utf16_positions() {
+        // The emoji takes two UTF-16 code units but is a single char.
+        let mut document = open("a = \"😀x\"\n");
+        document.apply_changes(&[change(Some(((0, 7), (0, 8))), "y")], 2);
+        assert_eq!(document.text(), "a = \"😀y\"\n");
+    }
+
+    #[test]
+    fn positions_are_clamped() {
+        let mut document = open("a = 1\r\nb = 2");
+        document.apply_changes(&[change(Some(((0, 99), (0, 99))), "0")], 2);
+        assert_eq!(document.text(), "a = 10\r\nb = 2");
+
+        document.apply_changes(&[change(Some(((5, 0), (5, 0))), "\n")], 3);
+        assert_eq!(document.text(), "a = 10\r\nb = 2\n");
+    }
+
+    #[test]
+    fn full_changes() {
+        let mut document = open("a = 1");
+        document.apply_changes(&[change(None, "b = 2")], 2);
+        assert_eq!(document.text(), "b = 2");
+    }
+}
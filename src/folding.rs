@@ -0,0 +1,141 @@
+//! The parts of a document that can be folded, for `textDocument/foldingRange`.
+
+use lsp_types::{FoldingRange, FoldingRangeKind};
+use rowan::{NodeOrToken, TextRange};
+
+use crate::{
+    lexer::SyntaxKind::*,
+    line_index::LineIndex,
+    parser::{SyntaxToken, AST},
+};
+
+/// Returns the sections of headers, multi-line arrays and strings, and runs of lines that
+/// hold only a comment, ordered by their first line.
+pub fn folding_ranges(ast: &AST, line_index: &LineIndex) -> Vec<FoldingRange> {
+    let mut ranges = Vec::new();
+    let mut fold = |range: TextRange, kind: Option<FoldingRangeKind>| {
+        let start_line = line_index.position(range.start()).line;
+        let end_line = line_index.position(range.end()).line;
+        if end_line > start_line {
+            ranges.push(FoldingRange {
+                start_line,
+                start_character: None,
+                end_line,
+                end_character: None,
+                kind,
+            });
+        }
+    };
+    // The current run of comment lines, and the line it ends on.
+    let mut comments: Option<(TextRange, u32)> = None;
+
+    for element in ast.node().descendants_with_tokens() {
+        match element {
+            NodeOrToken::Node(node) => match node.kind() {
+                // A section takes up the blank lines before the next header as well.
+                TableHeader | ArrayHeader => {
+                    let end = node
+                        .descendants_with_tokens()
+                        .filter_map(|it| it.into_token())
+                        .filter(|token| !matches!(token.kind(), Whitespace | Newline))
+                        .last();
+                    if let Some(end) = end {
+                        fold(
+                            TextRange::new(node.text_range().start(), end.text_range().end()),
+                            None,
+                        );
+                    }
+                }
+                Array => fold(node.text_range(), None),
+                _ => (),
+            },
+            NodeOrToken::Token(token) => match token.kind() {
+                MultiLineBasicString | MultiLineLiteralString => fold(token.text_range(), None),
+                Comment if starts_line(&token) => {
+                    let line = line_index.position(token.text_range().start()).line;
+                    comments = match comments {
+                        Some((run, last)) if last + 1 == line => {
+                            Some((run.cover(token.text_range()), line))
+                        }
+                        run => {
+                            if let Some((run, _)) = run {
+                                fold(run, Some(FoldingRangeKind::Comment));
+                            }
+                            Some((token.text_range(), line))
+                        }
+                    };
+                }
+                _ => (),
+            },
+        }
+    }
+    if let Some((run, _)) = comments {
+        fold(run, Some(FoldingRangeKind::Comment));
+    }
+
+    ranges.sort_by_key(|range| range.start_line);
+    ranges
+}
+
+/// Whether only whitespace comes before `token` on its line.
+fn starts_line(token: &SyntaxToken) -> bool {
+    let mut before = token.prev_token();
+    while let Some(token) = before {
+        match token.kind() {
+            Newline => return true,
+            Whitespace => before = token.prev_token(),
+            _ => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+    use crate::{line_index::PositionEncoding, parser::parse};
+
+    #[test]
+    fn ranges() {
+        let input = r#"# A document
+# about fruit.
+title = "fruit"
+
+[fruit]
+colors = [
+  "red",  # like apples
+  # not blue
+  "green",
+]
+about = """
+Round.
+"""
+
+[[fruit.variety]]
+name = "gala" # a comment after code
+# the end
+"#;
+        let ast = parse(input);
+        let line_index = LineIndex::new(input, PositionEncoding::Utf16);
+        let out: String = folding_ranges(&ast, &line_index)
+            .iter()
+            .map(|range| {
+                let kind = match range.kind {
+                    Some(FoldingRangeKind::Comment) => " comment",
+                    _ => "",
+                };
+                format!("{}..{}{}\n", range.start_line, range.end_line, kind)
+            })
+            .collect();
+        expect![[r#"
+            0..1 comment
+            4..12
+            5..9
+            10..12
+            14..16
+        "#]]
+        .assert_eq(&out);
+    }
+}
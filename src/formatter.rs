@@ -0,0 +1,726 @@
+//! Formatting on top of the lossless syntax tree. Keys and values are written exactly as they
+//! are, only the whitespace between them changes, so the formatted document always stands for
+//! the same values. Comments are kept, each on the line it belongs to.
+
+use lsp_types::{FormattingOptions, FormattingProperty};
+use rowan::{NodeOrToken, TextRange, TextSize};
+
+use crate::{
+    lexer::SyntaxKind::{self, *},
+    parser::{
+        ast::{self, AstNode},
+        SyntaxNode, TextEdit, AST,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Arrays that would make a line longer than this are split into one element per line.
+    pub column_width: usize,
+    /// One level of indentation.
+    pub indent: String,
+    /// Whether to indent `[a.b]` headers, and the entries below them, by their depth.
+    pub indent_tables: bool,
+    /// Whether the last element of an array that is split over lines gets a comma.
+    pub array_trailing_comma: bool,
+    /// How many blank lines to put in front of every header.
+    pub blank_lines_between_tables: usize,
+    /// Whether to line up the `=` of consecutive key/value pairs.
+    pub align_keys: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            column_width: 80,
+            indent: "  ".to_string(),
+            indent_tables: false,
+            array_trailing_comma: true,
+            blank_lines_between_tables: 1,
+            align_keys: false,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Takes the indentation from the options of a formatting request, and the rest from
+    /// its properties, under the names `columnWidth`, `indentTables`, `arrayTrailingComma`,
+    /// `blankLinesBetweenTables` and `alignKeys`.
+    pub fn from_lsp(options: &FormattingOptions) -> FormatOptions {
+        let properties = &options.properties;
+        let number = |name: &str| match properties.get(name) {
+            Some(FormattingProperty::Number(n)) if *n >= 0 => Some(*n as usize),
+            _ => None,
+        };
+        let flag = |name: &str| match properties.get(name) {
+            Some(FormattingProperty::Bool(b)) => Some(*b),
+            _ => None,
+        };
+
+        let default = FormatOptions::default();
+        FormatOptions {
+            column_width: number("columnWidth").unwrap_or(default.column_width),
+            indent: if options.insert_spaces {
+                " ".repeat(options.tab_size as usize)
+            } else {
+                "\t".to_string()
+            },
+            indent_tables: flag("indentTables").unwrap_or(default.indent_tables),
+            array_trailing_comma: flag("arrayTrailingComma")
+                .unwrap_or(default.array_trailing_comma),
+            blank_lines_between_tables: number("blankLinesBetweenTables")
+                .unwrap_or(default.blank_lines_between_tables),
+            align_keys: flag("alignKeys").unwrap_or(default.align_keys),
+        }
+    }
+}
+
+/// Formats a whole document. Returns `None` if it has syntax errors, as there is no telling
+/// what the document was meant to look like then.
+pub fn format(ast: &AST, options: &FormatOptions) -> Option<String> {
+    if !ast.errors().is_empty() {
+        return None;
+    }
+    let root = ast.node();
+    let newline = root
+        .descendants_with_tokens()
+        .find(|it| it.kind() == Newline)
+        .and_then(|it| it.into_token())
+        .map_or("\n".to_string(), |it| it.text().to_string());
+
+    let formatter = Formatter {
+        options,
+        newline: &newline,
+    };
+    Some(formatter.document(arrange(lines(&root, options), options)))
+}
+
+/// The edits that format the lines of the document overlapping `range`, or the whole document
+/// without one. Each edit replaces a run of lines that changed.
+pub fn format_edits(
+    ast: &AST,
+    options: &FormatOptions,
+    range: Option<TextRange>,
+) -> Option<Vec<TextEdit>> {
+    let formatted = format(ast, options)?;
+    let text = ast.node().to_string();
+    let mut edits = line_diff(&text, &formatted);
+    if let Some(range) = range {
+        edits.retain(|edit| {
+            let delete = edit.delete;
+            if delete.is_empty() {
+                range.contains_inclusive(delete.start())
+            } else {
+                delete.start() < range.end() && range.start() < delete.end()
+                    || range.is_empty() && delete.contains(range.start())
+            }
+        });
+    }
+    Some(edits)
+}
+
+#[derive(Debug)]
+enum LineKind {
+    Blank,
+    Comment(String),
+    Header {
+        key: ast::Key,
+        array: bool,
+        comment: Option<String>,
+    },
+    Entry {
+        kv: ast::KeyValue,
+        comment: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+struct Line {
+    kind: LineKind,
+    depth: usize,
+}
+
+/// Splits the document into its lines, dropping all whitespace.
+fn lines(root: &SyntaxNode, options: &FormatOptions) -> Vec<Line> {
+    // Headers are followed by the entries of their section, flattened.
+    let mut elements = Vec::new();
+    for child in root.children_with_tokens() {
+        match &child {
+            NodeOrToken::Node(node) if is_header(node.kind()) => {
+                elements.push(child.clone());
+                elements.extend(
+                    node.children_with_tokens()
+                        .filter(|it| !matches!(it.kind(), LBracket | RBracket | SyntaxKind::Key)),
+                );
+            }
+            _ => elements.push(child),
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut item = None;
+    let mut comment = None;
+    let mut empty = true;
+    let mut depth = 0;
+
+    for element in elements {
+        match element {
+            NodeOrToken::Node(node) => {
+                if let Some(kv) = ast::KeyValue::cast(node.clone()) {
+                    item = Some(LineKind::Entry { kv, comment: None });
+                } else if let Some(key) = node.children().find_map(ast::Key::cast) {
+                    if options.indent_tables {
+                        depth = key.segments().count().saturating_sub(1);
+                    }
+                    item = Some(LineKind::Header {
+                        key,
+                        array: node.kind() == ArrayHeader,
+                        comment: None,
+                    });
+                }
+                empty = false;
+            }
+            NodeOrToken::Token(token) => match token.kind() {
+                Comment if item.is_some() => comment = Some(token.text().to_string()),
+                Comment => {
+                    let kind = LineKind::Comment(token.text().to_string());
+                    lines.push(Line { kind, depth });
+                    empty = false;
+                }
+                Newline => {
+                    if empty {
+                        lines.push(Line {
+                            kind: LineKind::Blank,
+                            depth: 0,
+                        });
+                    }
+                    flush(&mut lines, &mut item, comment.take(), depth);
+                    empty = true;
+                }
+                _ => (),
+            },
+        }
+    }
+    flush(&mut lines, &mut item, comment.take(), depth);
+    lines
+}
+
+/// Ends the line of `item`, if there is one.
+fn flush(
+    lines: &mut Vec<Line>,
+    item: &mut Option<LineKind>,
+    comment: Option<String>,
+    depth: usize,
+) {
+    let kind = match item.take() {
+        Some(LineKind::Header { key, array, .. }) => LineKind::Header {
+            key,
+            array,
+            comment,
+        },
+        Some(LineKind::Entry { kv, .. }) => LineKind::Entry { kv, comment },
+        _ => return,
+    };
+    lines.push(Line { kind, depth });
+}
+
+/// Puts the blank lines where they belong: at most one in a row, the configured number in
+/// front of headers and the comments directly above them, and none at the ends.
+fn arrange(lines: Vec<Line>, options: &FormatOptions) -> Vec<Line> {
+    let is_blank = |line: &Line| matches!(line.kind, LineKind::Blank);
+    let mut arranged: Vec<Line> = Vec::with_capacity(lines.len());
+    for line in lines {
+        match line.kind {
+            LineKind::Blank => {
+                if arranged.last().is_some_and(|last| !is_blank(last)) {
+                    arranged.push(line);
+                }
+            }
+            LineKind::Header { .. } => {
+                let start = arranged
+                    .iter()
+                    .rposition(|line| !matches!(line.kind, LineKind::Comment(_)))
+                    .map_or(0, |i| i + 1);
+                let mut comments = arranged.split_off(start);
+                for comment in &mut comments {
+                    comment.depth = line.depth;
+                }
+                while arranged.last().is_some_and(is_blank) {
+                    arranged.pop();
+                }
+                if !arranged.is_empty() {
+                    for _ in 0..options.blank_lines_between_tables {
+                        arranged.push(Line {
+                            kind: LineKind::Blank,
+                            depth: 0,
+                        });
+                    }
+                }
+                arranged.append(&mut comments);
+                arranged.push(line);
+            }
+            _ => arranged.push(line),
+        }
+    }
+    while arranged.last().is_some_and(is_blank) {
+        arranged.pop();
+    }
+    arranged
+}
+
+struct Formatter<'a> {
+    options: &'a FormatOptions,
+    newline: &'a str,
+}
+
+impl Formatter<'_> {
+    fn document(&self, lines: Vec<Line>) -> String {
+        let mut out = String::new();
+        let widths = self.key_widths(&lines);
+        for (line, width) in lines.iter().zip(widths) {
+            if !matches!(line.kind, LineKind::Blank) {
+                out.push_str(&self.options.indent.repeat(line.depth));
+            }
+            match &line.kind {
+                LineKind::Blank => (),
+                LineKind::Comment(comment) => out.push_str(comment),
+                LineKind::Header {
+                    key,
+                    array,
+                    comment,
+                } => {
+                    let (open, close) = if *array { ("[[", "]]") } else { ("[", "]") };
+                    out.push_str(open);
+                    out.push_str(&key_text(key));
+                    out.push_str(close);
+                    trailing_comment(&mut out, comment);
+                }
+                LineKind::Entry { kv, comment } => {
+                    self.key_value(&mut out, kv, width, line.depth, false);
+                    trailing_comment(&mut out, comment);
+                }
+            }
+            out.push_str(self.newline);
+        }
+        out
+    }
+
+    /// The width to pad the key of every line to, which is only ever more than the width of
+    /// the key itself for aligned runs of key/value pairs.
+    fn key_widths(&self, lines: &[Line]) -> Vec<usize> {
+        let mut widths: Vec<_> = lines
+            .iter()
+            .map(|line| match &line.kind {
+                LineKind::Entry { kv, .. } => kv.key().map_or(0, |key| width(&key_text(&key))),
+                _ => 0,
+            })
+            .collect();
+        if !self.options.align_keys {
+            return widths;
+        }
+
+        let mut start = 0;
+        while start < lines.len() {
+            let len = lines[start..]
+                .iter()
+                .take_while(|line| matches!(line.kind, LineKind::Entry { .. }))
+                .count();
+            let run = &mut widths[start..start + len];
+            let max = run.iter().copied().max().unwrap_or(0);
+            run.iter_mut().for_each(|width| *width = max);
+            start += len.max(1);
+        }
+        widths
+    }
+
+    fn key_value(
+        &self,
+        out: &mut String,
+        kv: &ast::KeyValue,
+        key_width: usize,
+        depth: usize,
+        inline: bool,
+    ) {
+        let key = kv.key().map(|key| key_text(&key)).unwrap_or_default();
+        out.push_str(&key);
+        out.push_str(&" ".repeat(key_width.saturating_sub(width(&key))));
+        out.push_str(" = ");
+        if let Some(value) = kv.value() {
+            self.value(out, &value, depth, inline);
+        }
+    }
+
+    /// Writes `value` at the end of `out`, on a line indented by `depth`. Values in inline
+    /// tables are kept on a single line unless they contain comments.
+    fn value(&self, out: &mut String, value: &ast::Value, depth: usize, inline: bool) {
+        match value {
+            ast::Value::Scalar(token) => out.push_str(token.text()),
+            ast::Value::InlineTable(table) => {
+                let entries: Vec<_> = table.entries().collect();
+                if entries.is_empty() {
+                    out.push_str("{}");
+                    return;
+                }
+                out.push_str("{ ");
+                for (i, kv) in entries.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.key_value(out, kv, 0, depth, true);
+                }
+                out.push_str(" }");
+            }
+            ast::Value::Array(array) => {
+                let has_comments = array
+                    .syntax()
+                    .descendants_with_tokens()
+                    .any(|it| it.kind() == Comment);
+                if !has_comments {
+                    let flat = flat(value);
+                    let column = width(&out[out.rfind('\n').map_or(0, |i| i + 1)..]);
+                    if inline || column + width(&flat) <= self.options.column_width {
+                        out.push_str(&flat);
+                        return;
+                    }
+                }
+                self.expanded_array(out, array, depth, inline);
+            }
+        }
+    }
+
+    fn expanded_array(&self, out: &mut String, array: &ast::Array, depth: usize, inline: bool) {
+        let items = array_items(array);
+        let last = items
+            .iter()
+            .rposition(|item| matches!(item, ArrayItem::Value { .. }));
+        let indent = self.options.indent.repeat(depth + 1);
+
+        out.push('[');
+        for (i, item) in items.iter().enumerate() {
+            out.push_str(self.newline);
+            out.push_str(&indent);
+            match item {
+                ArrayItem::Comment(comment) => out.push_str(comment),
+                ArrayItem::Value { value, comment } => {
+                    self.value(out, value, depth + 1, inline);
+                    if Some(i) != last || self.options.array_trailing_comma {
+                        out.push(',');
+                    }
+                    trailing_comment(out, comment);
+                }
+            }
+        }
+        out.push_str(self.newline);
+        out.push_str(&self.options.indent.repeat(depth));
+        out.push(']');
+    }
+}
+
+enum ArrayItem {
+    Value {
+        value: ast::Value,
+        comment: Option<String>,
+    },
+    Comment(String),
+}
+
+/// The values of an array, and its comments. Comments on the same line as a value belong to
+/// that value.
+fn array_items(array: &ast::Array) -> Vec<ArrayItem> {
+    let mut items = Vec::new();
+    let mut last_value = None;
+    for element in array.syntax().children_with_tokens() {
+        if let Some(value) = ast::Value::cast(element.clone()) {
+            last_value = Some(items.len());
+            items.push(ArrayItem::Value {
+                value,
+                comment: None,
+            });
+            continue;
+        }
+        match element.kind() {
+            Newline => last_value = None,
+            Comment => {
+                let text = element.to_string();
+                match last_value.map(|i| &mut items[i]) {
+                    Some(ArrayItem::Value { comment, .. }) => *comment = Some(text),
+                    _ => items.push(ArrayItem::Comment(text)),
+                }
+            }
+            _ => (),
+        }
+    }
+    items
+}
+
+/// The value on a single line, apart from the line breaks in multi-line strings.
+fn flat(value: &ast::Value) -> String {
+    match value {
+        ast::Value::Scalar(token) => token.text().to_string(),
+        ast::Value::InlineTable(table) => {
+            let entries: Vec<_> = table
+                .entries()
+                .map(|kv| {
+                    let key = kv.key().map(|key| key_text(&key)).unwrap_or_default();
+                    let value = kv.value().map(|value| flat(&value)).unwrap_or_default();
+                    format!("{} = {}", key, value)
+                })
+                .collect();
+            if entries.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", entries.join(", "))
+            }
+        }
+        ast::Value::Array(array) => {
+            let values: Vec<_> = array.values().map(|value| flat(&value)).collect();
+            format!("[{}]", values.join(", "))
+        }
+    }
+}
+
+fn key_text(key: &ast::Key) -> String {
+    let segments: Vec<_> = key
+        .segments()
+        .map(|segment| segment.syntax().to_string())
+        .collect();
+    segments.join(".")
+}
+
+fn trailing_comment(out: &mut String, comment: &Option<String>) {
+    if let Some(comment) = comment {
+        out.push(' ');
+        out.push_str(comment);
+    }
+}
+
+fn is_header(kind: SyntaxKind) -> bool {
+    kind == TableHeader || kind == ArrayHeader
+}
+
+/// How many columns the first line of `text` takes.
+fn width(text: &str) -> usize {
+    text.split('\n').next().unwrap_or_default().chars().count()
+}
+
+/// The edits that turn `old` into `new`, one for every run of changed lines.
+fn line_diff(old: &str, new: &str) -> Vec<TextEdit> {
+    let old_lines: Vec<_> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<_> = new.split_inclusive('\n').collect();
+
+    let prefix = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old_lines[prefix..old_lines.len() - suffix];
+    let new_mid = &new_lines[prefix..new_lines.len() - suffix];
+
+    // Pairs of lines that stay, by index into the changed lines, found as a longest common
+    // subsequence. Huge changes are replaced as a whole rather than spending time on them.
+    let mut kept = Vec::new();
+    if old_mid.len().saturating_mul(new_mid.len()) <= 4_000_000 {
+        let (n, m) = (old_mid.len(), new_mid.len());
+        let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * (m + 1) + j] = if old_mid[i] == new_mid[j] {
+                    lcs[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if old_mid[i] == new_mid[j] {
+                kept.push((i, j));
+                i += 1;
+                j += 1;
+            } else if lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+    kept.push((old_mid.len(), new_mid.len()));
+
+    let offsets: Vec<TextSize> = std::iter::once(0.into())
+        .chain(old_lines.iter().scan(TextSize::from(0), |offset, line| {
+            *offset += TextSize::of(*line);
+            Some(*offset)
+        }))
+        .collect();
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in kept {
+        if next_i - i == next_j - j {
+            // Lines that were changed one by one are edited one by one, so that formatting
+            // a range does not touch the lines around it.
+            for k in 0..next_i - i {
+                edits.push(TextEdit {
+                    delete: TextRange::new(offsets[prefix + i + k], offsets[prefix + i + k + 1]),
+                    insert: new_mid[j + k].to_string(),
+                });
+            }
+        } else {
+            edits.push(TextEdit {
+                delete: TextRange::new(offsets[prefix + i], offsets[prefix + next_i]),
+                insert: new_mid[j..next_j].concat(),
+            });
+        }
+        i = next_i + 1;
+        j = next_j + 1;
+    }
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, fs, path::PathBuf};
+
+    use expect_test::{expect, expect_file, Expect};
+
+    use super::*;
+    use crate::{parser::parse, semantic};
+
+    fn check(input: &str, options: &FormatOptions, expect: Expect) {
+        let formatted = format(&parse(input), options).expect("input has syntax errors");
+        expect.assert_eq(&formatted);
+        check_stable(input, &formatted, options);
+    }
+
+    /// Formatting again does not change anything, and the values stay the same.
+    fn check_stable(input: &str, formatted: &str, options: &FormatOptions) {
+        let ast = parse(formatted);
+        assert_eq!(ast.errors(), Vec::new(), "{}", formatted);
+        assert_eq!(format(&ast, options).as_deref(), Some(formatted));
+        assert_eq!(
+            plain_table(&semantic::lower(&parse(input).root()).root),
+            plain_table(&semantic::lower(&ast.root()).root)
+        );
+    }
+
+    /// The value tree without ranges, which formatting moves around.
+    fn plain_table(table: &semantic::Table) -> String {
+        let entries: Vec<_> = table
+            .entries
+            .iter()
+            .map(|entry| format!("{:?} = {}", entry.key, plain(&entry.value.kind)))
+            .collect();
+        format!("{{{}}}", entries.join(", "))
+    }
+
+    fn plain(kind: &semantic::ValueKind) -> String {
+        match kind {
+            semantic::ValueKind::Table(table) => plain_table(table),
+            semantic::ValueKind::Array(array) => {
+                let values: Vec<_> = array.values.iter().map(|it| plain(&it.kind)).collect();
+                format!("[{}]", values.join(", "))
+            }
+            kind => format!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn formatter_dir() {
+        let dir: PathBuf = [env!("CARGO_MANIFEST_DIR"), "test_data", "formatter"]
+            .iter()
+            .collect();
+        for entry in dir.read_dir().expect("Failed to read dir") {
+            let path = entry.expect("Failed to read entry").path();
+            if path.extension() != Some(OsStr::new("toml")) {
+                continue;
+            }
+            let input = fs::read_to_string(&path).expect("Failed to read to string");
+            let options = FormatOptions::default();
+            let formatted = format(&parse(&input), &options).expect("input has syntax errors");
+            expect_file![path.with_extension("expect")].assert_eq(&formatted);
+            check_stable(&input, &formatted, &options);
+        }
+    }
+
+    #[test]
+    fn options() {
+        let input = r#"# settings
+name="x"
+long_name = 1
+[servers.alpha]
+ip = "10.0.0.1"
+ports=[8000,8001,8002]
+[other]
+"#;
+        let options = FormatOptions {
+            column_width: 20,
+            indent: "    ".to_string(),
+            indent_tables: true,
+            array_trailing_comma: false,
+            blank_lines_between_tables: 2,
+            align_keys: true,
+        };
+        check(
+            input,
+            &options,
+            expect![[r#"
+                # settings
+                name      = "x"
+                long_name = 1
+
+
+                    [servers.alpha]
+                    ip    = "10.0.0.1"
+                    ports = [
+                        8000,
+                        8001,
+                        8002
+                    ]
+
+
+                [other]
+            "#]],
+        );
+    }
+
+    #[test]
+    fn keeps_line_endings() {
+        let input = "a = 1\r\n\r\n\r\n[t]\r\nb = [1,\r\n# c\r\n2]\r\n";
+        let options = FormatOptions::default();
+        let formatted = format(&parse(input), &options).unwrap();
+        assert_eq!(
+            formatted,
+            "a = 1\r\n\r\n[t]\r\nb = [\r\n  1,\r\n  # c\r\n  2,\r\n]\r\n"
+        );
+        check_stable(input, &formatted, &options);
+    }
+
+    #[test]
+    fn refuses_syntax_errors() {
+        assert_eq!(format(&parse("a = [1,"), &FormatOptions::default()), None);
+    }
+
+    #[test]
+    fn range_edits() {
+        let input = "a=1\nb=2\nc=3\n";
+        let ast = parse(input);
+        let options = FormatOptions::default();
+        let edits = format_edits(&ast, &options, None).unwrap();
+        let inserts: Vec<_> = edits.iter().map(|edit| edit.insert.as_str()).collect();
+        assert_eq!(inserts, ["a = 1\n", "b = 2\n", "c = 3\n"]);
+
+        let edits = format_edits(&ast, &options, Some(TextRange::new(4.into(), 5.into())));
+        assert_eq!(
+            edits.unwrap(),
+            [TextEdit {
+                delete: TextRange::new(4.into(), 8.into()),
+                insert: "b = 2\n".to_string(),
+            }]
+        );
+    }
+}
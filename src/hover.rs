@@ -0,0 +1,369 @@
+//! The path and type of the key or value under the cursor, for `textDocument/hover`, with
+//! the documentation of its schema.
+
+use lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind};
+use rowan::{TextRange, TextSize};
+use serde_json::Value as Json;
+
+use crate::{
+    line_index::LineIndex,
+    parser::{
+        ast::{self, AstNode},
+        AST,
+    },
+    schema::{self, Schema},
+    semantic::{self, Path, PathSegment},
+};
+
+pub fn hover(
+    ast: &AST,
+    document: &semantic::Document,
+    schema: Option<&Schema>,
+    line_index: &LineIndex,
+    offset: TextSize,
+) -> Option<Hover> {
+    let node = ast.node();
+    let tokens = node.token_at_offset(offset);
+    let token = match tokens.clone().right_biased() {
+        Some(token) if !token.kind().is_trivia() => token,
+        _ => tokens.left_biased()?,
+    };
+    if token.kind().is_trivia() {
+        return None;
+    }
+    let at = token.text_range().start();
+
+    let entry = ast
+        .root()
+        .entries()
+        .find(|entry| entry.syntax().text_range().contains(at))?;
+    let (header, entries): (_, Vec<_>) = match entry {
+        ast::Entry::KeyValue(kv) => (None, vec![kv]),
+        ast::Entry::Table(header) => (
+            Some((header.syntax().clone(), header.key()?)),
+            header.entries().collect(),
+        ),
+        ast::Entry::ArrayOfTables(header) => (
+            Some((header.syntax().clone(), header.key()?)),
+            header.entries().collect(),
+        ),
+    };
+
+    let mut section = Path::default();
+    let (path, range) = match header {
+        Some((node, key)) => {
+            let start = node.text_range().start();
+            let names = key_names(&key)?;
+            section = document.root.resolve_header(&names, start);
+            if key.text_range().contains(at) {
+                let i = key
+                    .segments()
+                    .position(|segment| segment.text_range().contains(at))?;
+                let segment = key.segments().nth(i)?;
+                let path = document.root.resolve_header(&names[..=i], start);
+                (path, segment.text_range())
+            } else {
+                let kv = entries.iter().find(|kv| kv.text_range().contains(at))?;
+                key_value(kv, at, section.clone())?
+            }
+        }
+        None => key_value(&entries[0], at, Path::default())?,
+    };
+
+    let found = document.root.lookup(&path.0);
+    let mut value = format!("`{}`", path);
+    if let Some(found) = found {
+        value.push_str(&format!(": {}", found.kind.type_name()));
+    }
+    // Sections of arrays of tables all have the same key, so which element one of them
+    // belongs to is not obvious from the document.
+    if let Some((PathSegment::Index(i), array)) = section.0.split_last() {
+        value.push_str(&format!(
+            "\n\nIn element {} of the array of tables `{}`",
+            i,
+            Path(array.to_vec())
+        ));
+    }
+    if let Some(schema) = schema {
+        let mut schemas = schema.resolve(&path.0);
+        // Of the branches of `oneOf` and the like, only the ones for the type of the value
+        // describe it.
+        if let Some(found) = found {
+            schemas.retain(|schema| schema::allows(schema, &found.kind));
+        }
+        if let Some(docs) = schema_docs(&schemas) {
+            value.push_str("\n\n---\n\n");
+            value.push_str(&docs);
+        }
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(line_index.range(range)),
+    })
+}
+
+/// Renders the first `title`, `description` and `default` of `schemas`, with all of their
+/// `examples` and the types they allow.
+fn schema_docs(schemas: &[&Json]) -> Option<String> {
+    let first = |name| schemas.iter().find_map(|schema| schema.get(name));
+    let literal = |value: &Json| match schema::literal(value) {
+        Some(literal) => format!("`{}`", literal),
+        None => format!("`{}`", value),
+    };
+
+    let mut sections = Vec::new();
+    if let Some(title) = first("title").and_then(Json::as_str) {
+        sections.push(format!("**{}**", title));
+    }
+    if let Some(description) = first("description").and_then(Json::as_str) {
+        sections.push(description.to_string());
+    }
+    let mut types: Vec<_> = schemas
+        .iter()
+        .flat_map(|schema| schema::types(schema))
+        .map(schema::type_name)
+        .collect();
+    types.dedup();
+    if !types.is_empty() {
+        let types: Vec<_> = types.iter().map(|name| format!("`{}`", name)).collect();
+        sections.push(format!("Type: {}", types.join(" | ")));
+    }
+    if let Some(default) = first("default") {
+        sections.push(format!("Default: {}", literal(default)));
+    }
+    let examples: Vec<_> = schemas
+        .iter()
+        .filter_map(|schema| schema.get("examples").and_then(Json::as_array))
+        .flatten()
+        .map(literal)
+        .collect();
+    if !examples.is_empty() {
+        sections.push(format!("Examples: {}", examples.join(", ")));
+    }
+
+    if sections.is_empty() {
+        None
+    } else {
+        Some(sections.join("\n\n"))
+    }
+}
+
+fn key_names(key: &ast::Key) -> Option<Vec<String>> {
+    key.segments().map(|segment| segment.name()).collect()
+}
+
+/// The path and range of what is at `at` in `kv`, which is in the table at `path`.
+fn key_value(kv: &ast::KeyValue, at: TextSize, mut path: Path) -> Option<(Path, TextRange)> {
+    let key = kv.key()?;
+    for segment in key.segments() {
+        path.0.push(PathSegment::Key(segment.name()?));
+        if segment.text_range().contains(at) {
+            return Some((path, segment.text_range()));
+        }
+    }
+    let value = kv.value()?;
+    if !value.text_range().contains(at) {
+        return None;
+    }
+    value_path(&value, at, path)
+}
+
+fn value_path(value: &ast::Value, at: TextSize, mut path: Path) -> Option<(Path, TextRange)> {
+    match value {
+        ast::Value::Array(array) => {
+            let element = array
+                .values()
+                .enumerate()
+                .find(|(_, value)| value.text_range().contains(at));
+            if let Some((i, element)) = element {
+                path.0.push(PathSegment::Index(i));
+                return value_path(&element, at, path);
+            }
+        }
+        ast::Value::InlineTable(table) => {
+            if let Some(kv) = table.entries().find(|kv| kv.text_range().contains(at)) {
+                return key_value(&kv, at, path);
+            }
+        }
+        ast::Value::Scalar(_) => (),
+    }
+    Some((path, value.text_range()))
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::{expect, Expect};
+
+    use super::*;
+    use crate::{line_index::PositionEncoding, parser::parse};
+
+    /// Hovers at the `$0` in `input`, with the schema if there is one.
+    fn check_with(input: &str, schema: Option<&Schema>, expect: Expect) {
+        let offset = input.find("$0").expect("no cursor in input");
+        let input = input.replace("$0", "");
+        let ast = parse(&input);
+        let document = semantic::lower(&ast.root());
+        let line_index = LineIndex::new(&input, PositionEncoding::Utf8);
+
+        let actual = match hover(&ast, &document, schema, &line_index, (offset as u32).into()) {
+            Some(hover) => {
+                let value = match hover.contents {
+                    HoverContents::Markup(markup) => markup.value,
+                    contents => panic!("unexpected contents {:?}", contents),
+                };
+                let range = line_index.text_range(hover.range.unwrap());
+                format!("{} @{:?}", value, range)
+            }
+            None => "none".to_string(),
+        };
+        expect.assert_eq(&actual);
+    }
+
+    fn check(input: &str, expect: Expect) {
+        check_with(input, None, expect)
+    }
+
+    #[test]
+    fn keys_and_values() {
+        let input = r#"[dependencies]
+serde = { version = "1", features = ["derive", "rc", "std"] }
+"#;
+        check(
+            &input.replace("\"std\"", "\"s$0td\""),
+            expect![["`dependencies.serde.features[2]`: string @68..73"]],
+        );
+        check(
+            &input.replace("serde", "se$0rde"),
+            expect![["`dependencies.serde`: table @15..20"]],
+        );
+        check(
+            &input.replace("[\"derive", "$0[\"derive"),
+            expect![["`dependencies.serde.features`: array @51..74"]],
+        );
+        check(&input.replace("= {", "$0= {"), expect![["none"]]);
+    }
+
+    #[test]
+    fn headers() {
+        let input = r#"a.b = 1
+[[fruit]]
+name = "apple"
+[[fruit]]
+name = "banana"
+[fruit.color]
+"#;
+        check(&input.replace("a.b", "$0a.b"), expect![["`a`: table @0..1"]]);
+        check(
+            &input.replace("a.b", "a.$0b"),
+            expect![["`a.b`: integer @2..3"]],
+        );
+        check(
+            &input.replace("banana", "ban$0ana"),
+            expect![[r#"
+            `fruit[1].name`: string
+
+            In element 1 of the array of tables `fruit` @50..58"#]],
+        );
+        check(
+            &input.replace("[[fruit]]\nname = \"b", "[[fr$0uit]]\nname = \"b"),
+            expect![[r#"
+                `fruit[1]`: table
+
+                In element 1 of the array of tables `fruit` @35..40"#]],
+        );
+        check(
+            &input.replace("[fruit.color]", "[fruit.co$0lor]"),
+            expect![["`fruit[1].color`: table @66..71"]],
+        );
+        check(
+            &input.replace("[fruit.color]", "[fr$0uit.color]"),
+            expect![["`fruit[1]`: table @60..65"]],
+        );
+    }
+
+    #[test]
+    fn schema_documentation() {
+        let schema = Schema::new(serde_json::json!({
+            "properties": {
+                "name": {
+                    "title": "Name",
+                    "description": "The name of the package.",
+                    "type": "string",
+                    "examples": ["toml-lsp"],
+                },
+                "bin": {
+                    "type": "array",
+                    "items": {
+                        "properties": {
+                            "test": {
+                                "description": "Whether to test the binary.",
+                                "type": "boolean",
+                                "default": true,
+                            },
+                        },
+                    },
+                },
+                "dependency": {
+                    "oneOf": [
+                        { "type": "string", "description": "The version requirement." },
+                        { "type": "object", "description": "The detailed requirements." },
+                    ],
+                },
+            },
+        }));
+        let input = r#"name = "toml-lsp"
+dependency = "1"
+[[bin]]
+[[bin]]
+test = false
+"#;
+        check_with(
+            &input.replace("name", "na$0me"),
+            Some(&schema),
+            expect![[r#"
+                `name`: string
+
+                ---
+
+                **Name**
+
+                The name of the package.
+
+                Type: `string`
+
+                Examples: `"toml-lsp"` @0..4"#]],
+        );
+        check_with(
+            &input.replace("test", "te$0st"),
+            Some(&schema),
+            expect![[r#"
+                `bin[1].test`: boolean
+
+                In element 1 of the array of tables `bin`
+
+                ---
+
+                Whether to test the binary.
+
+                Type: `boolean`
+
+                Default: `true` @51..55"#]],
+        );
+        check_with(
+            &input.replace("dependency", "dep$0endency"),
+            Some(&schema),
+            expect![[r#"
+                `dependency`: string
+
+                ---
+
+                The version requirement.
+
+                Type: `string` @18..28"#]],
+        );
+    }
+}
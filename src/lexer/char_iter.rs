@@ -47,6 +47,12 @@ impl<'a> CharIter<'a> {
         &self.input[self.start..self.end]
     }
 
+    /// The input that has not been consumed yet.
+    #[inline]
+    pub fn rest(&self) -> &'a str {
+        &self.input[self.end..]
+    }
+
     #[inline]
     pub fn ignore(&mut self) {
         self.start = self.end;
@@ -63,6 +69,27 @@ impl<'a> CharIter<'a> {
         }
     }
 
+    pub fn accept_if(&mut self, predicate: impl Fn(char) -> bool) -> bool {
+        match self.peek() {
+            Some(c) if predicate(c) => {
+                self.next();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn accept_str(&mut self, valid: &str) -> bool {
+        if self.rest().starts_with(valid) {
+            valid.chars().for_each(|_| {
+                self.next();
+            });
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn accept_while(&mut self, predicate: impl Fn(char) -> bool) {
         while let Some(c) = self.peek() {
             if !predicate(c) {
@@ -88,7 +115,7 @@ impl Iterator for CharIter<'_> {
     fn next(&mut self) -> Option<Self::Item> {
         let (end, c) = self.buffer.pop_front()?;
 
-        self.end = end + 1;
+        self.end = end + c.len_utf8();
 
         if let Some((idx, next_c)) = self.iter.next() {
             self.buffer.push_back((idx, next_c));
@@ -29,7 +29,8 @@ impl<'a> Lexer<'a> {
 
         let res = match c {
             '=' => Equal,
-            '\'' | '"' => self.string()?,
+            '"' => self.basic_string()?,
+            '\'' => self.literal_string()?,
             '[' => LBracket,
             ']' => RBracket,
             ',' => Comma,
@@ -37,10 +38,12 @@ impl<'a> Lexer<'a> {
             '{' => LBrace,
             '}' => RBrace,
             '\n' => Newline,
+            '\r' if self.chars.accept('\n') => Newline,
             '#' => self.comment()?,
+            '+' | '-' => self.signed()?,
             _ if is_whitespace(c) => self.whitespace()?,
             _ if is_letter(c) => self.key_word()?,
-            _ if is_number(c) => self.number()?,
+            _ if is_number(c) => self.date_time().or_else(|| self.number())?,
             _ => Error,
         };
         let slice = self.slice();
@@ -54,41 +57,230 @@ impl<'a> Lexer<'a> {
         Some(Whitespace)
     }
 
-    fn string(&mut self) -> Option<SyntaxKind> {
-        let op = self.chars.find_char('"');
-        if op.is_some() {
-            Some(String)
-        } else {
-            Some(Error)
+    /// Lexes a basic string, or a multi-line basic string if the opening quote is tripled.
+    /// An unterminated basic string only covers the rest of its line.
+    fn basic_string(&mut self) -> Option<SyntaxKind> {
+        if self.chars.accept_str("\"\"") {
+            return self.multi_line_basic_string();
+        }
+
+        loop {
+            match self.chars.peek() {
+                Some('"') => {
+                    self.chars.next();
+                    return Some(BasicString);
+                }
+                Some('\\') => {
+                    self.chars.next();
+                    // An escaped quote or backslash must not end the string. A backslash
+                    // right before the end of the line is left for validation to report.
+                    if !self.at_line_end() {
+                        self.chars.next();
+                    }
+                }
+                _ if self.at_line_end() => return Some(Error),
+                _ => {
+                    self.chars.next();
+                }
+            }
+        }
+    }
+
+    fn multi_line_basic_string(&mut self) -> Option<SyntaxKind> {
+        loop {
+            if self.chars.accept_str("\"\"\"") {
+                // Up to two quotes directly before the closing delimiter belong to the content.
+                self.chars.accept('"');
+                self.chars.accept('"');
+                return Some(MultiLineBasicString);
+            }
+
+            match self.chars.next() {
+                Some('\\') => {
+                    self.chars.next();
+                }
+                Some(_) => (),
+                None => return Some(Error),
+            }
         }
     }
 
+    /// Lexes a literal string, or a multi-line literal string if the opening quote is tripled.
+    /// Literal strings have no escapes, so the first matching quote always ends them.
+    fn literal_string(&mut self) -> Option<SyntaxKind> {
+        if self.chars.accept_str("''") {
+            return self.multi_line_literal_string();
+        }
+
+        loop {
+            match self.chars.peek() {
+                Some('\'') => {
+                    self.chars.next();
+                    return Some(LiteralString);
+                }
+                _ if self.at_line_end() => return Some(Error),
+                _ => {
+                    self.chars.next();
+                }
+            }
+        }
+    }
+
+    fn multi_line_literal_string(&mut self) -> Option<SyntaxKind> {
+        loop {
+            if self.chars.accept_str("'''") {
+                self.chars.accept('\'');
+                self.chars.accept('\'');
+                return Some(MultiLineLiteralString);
+            }
+
+            if self.chars.next().is_none() {
+                return Some(Error);
+            }
+        }
+    }
+
+    fn at_line_end(&self) -> bool {
+        let rest = self.chars.rest();
+        rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n")
+    }
+
     fn key_word(&mut self) -> Option<SyntaxKind> {
-        self.chars.accept_while(is_letter);
+        self.chars.accept_while(is_bare_key);
         let res = match self.slice() {
             "true" => True,
             "false" => False,
+            "inf" | "nan" => Float,
             _ => self.ident()?,
         };
         Some(res)
     }
 
     fn ident(&mut self) -> Option<SyntaxKind> {
-        self.chars.accept_while(is_letter);
+        self.chars.accept_while(is_bare_key);
         Some(Ident)
     }
 
+    fn signed(&mut self) -> Option<SyntaxKind> {
+        if self.chars.accept_str("inf") || self.chars.accept_str("nan") {
+            return self.finish_number(Float);
+        }
+
+        match self.chars.peek() {
+            Some(c) if is_number(c) => {
+                self.chars.next();
+                self.number()
+            }
+            _ if self.slice() == "-" => self.ident(),
+            _ => Some(Error),
+        }
+    }
+
+    /// Lexes the rest of a number after its first digit. Mistakes like leading zeros or
+    /// misplaced underscores are left in the token for validation to report.
     fn number(&mut self) -> Option<SyntaxKind> {
-        self.chars.accept_while(is_number);
-        Some(Number)
+        let unsigned = self.slice().trim_start_matches(['+', '-']);
+        if unsigned == "0" && self.chars.accept_if(|c| c == 'x' || c == 'o' || c == 'b') {
+            self.chars.accept_while(|c| c.is_ascii_hexdigit() || c == '_');
+            return self.finish_number(Integer);
+        }
+
+        self.chars.accept_while(is_digit_or_underscore);
+        let mut kind = Integer;
+
+        let mut rest = self.chars.rest().chars();
+        if rest.next() == Some('.') && rest.next().is_some_and(is_digit_or_underscore) {
+            self.chars.next();
+            self.chars.accept_while(is_digit_or_underscore);
+            kind = Float;
+        }
+
+        if self.chars.accept_if(|c| c == 'e' || c == 'E') {
+            self.chars.accept_if(|c| c == '+' || c == '-');
+            self.chars.accept_while(is_digit_or_underscore);
+            kind = Float;
+        }
+
+        self.finish_number(kind)
+    }
+
+    /// Lexes an RFC 3339 date, time or date-time after its first digit, if the input looks
+    /// like one. Impossible values like hour 25 are left for validation to report.
+    fn date_time(&mut self) -> Option<SyntaxKind> {
+        if self.accept_pattern("ddd-dd-dd") {
+            let rest = self.chars.rest();
+            let has_time = rest.starts_with(['T', 't', ' '])
+                && matches_pattern(&rest[1..], "dd:dd");
+            if !has_time {
+                return Some(LocalDate);
+            }
+
+            self.chars.next();
+            self.accept_pattern("dd:dd");
+            self.time_rest();
+            if self.chars.accept_if(|c| c == 'Z' || c == 'z')
+                || self.accept_pattern("+dd:dd")
+                || self.accept_pattern("-dd:dd")
+            {
+                Some(OffsetDateTime)
+            } else {
+                Some(LocalDateTime)
+            }
+        } else if self.accept_pattern("d:dd") {
+            self.time_rest();
+            Some(LocalTime)
+        } else {
+            None
+        }
+    }
+
+    /// Lexes the optional seconds and fraction of a time.
+    fn time_rest(&mut self) {
+        if self.accept_pattern(":dd") && matches_pattern(self.chars.rest(), ".d") {
+            self.chars.next();
+            self.chars.accept_while(is_number);
+        }
+    }
+
+    /// Accepts the next characters if they match `pattern`, where `d` stands for any digit.
+    fn accept_pattern(&mut self, pattern: &str) -> bool {
+        if matches_pattern(self.chars.rest(), pattern) {
+            pattern.chars().for_each(|_| {
+                self.chars.next();
+            });
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Something that starts like a number but goes on with bare key characters, like `1st`
+    /// or `-info`, can only be a key.
+    fn finish_number(&mut self, kind: SyntaxKind) -> Option<SyntaxKind> {
+        let continues_as_key = self.chars.peek().is_some_and(is_bare_key)
+            && self.slice().chars().all(is_bare_key);
+        if continues_as_key {
+            self.ident()
+        } else {
+            Some(kind)
+        }
     }
 
     fn comment(&mut self) -> Option<SyntaxKind> {
-        self.chars.accept_until(|c| c == '\n');
+        // A `\r` is only allowed as part of a line break, which is not part of the comment.
+        self.chars.accept_until(|c| c == '\n' || c == '\r');
         Some(Comment)
     }
 }
 
+fn matches_pattern(input: &str, pattern: &str) -> bool {
+    input.len() >= pattern.len()
+        && input.bytes().zip(pattern.bytes()).all(|(c, p)| match p {
+            b'd' => c.is_ascii_digit(),
+            _ => c == p,
+        })
+}
+
 fn is_letter(c: char) -> bool {
     c.is_alphabetic() || c == '_' || c == '-'
 }
@@ -98,7 +290,15 @@ const fn is_whitespace(c: char) -> bool {
 }
 
 fn is_number(c: char) -> bool {
-    c.is_digit(10)
+    c.is_ascii_digit()
+}
+
+fn is_digit_or_underscore(c: char) -> bool {
+    is_number(c) || c == '_'
+}
+
+fn is_bare_key(c: char) -> bool {
+    is_letter(c) || is_number(c)
 }
 
 impl<'a> Iterator for Lexer<'a> {
@@ -114,13 +314,8 @@ mod tests {
     use super::*;
 
     fn test_lexer(input: &str, expected_tokens: &[(SyntaxKind, &str)]) {
-        let lexer = Lexer::new(input);
-        lexer
-            .into_iter()
-            .zip(expected_tokens.iter())
-            .for_each(|(got, &expected)| {
-                assert_eq!(got, expected);
-            })
+        let got: Vec<_> = Lexer::new(input).collect();
+        assert_eq!(got, expected_tokens);
     }
 
     #[test]
@@ -165,11 +360,19 @@ this_key = "a string""#,
                 (Whitespace, " "),
                 (Equal, "="),
                 (Whitespace, " "),
-                (String, "\"a string\""),
+                (BasicString, "\"a string\""),
             ],
         )
     }
 
+    #[test]
+    fn test_comment_before_crlf() {
+        test_lexer(
+            "# a comment\r\nx",
+            &[(Comment, "# a comment"), (Newline, "\r\n"), (Ident, "x")],
+        )
+    }
+
     #[test]
     fn test_number() {
         test_lexer(
@@ -182,7 +385,7 @@ another_key = 12345"#,
                 (Whitespace, " "),
                 (Equal, "="),
                 (Whitespace, " "),
-                (Number, "12345"),
+                (Integer, "12345"),
             ],
         )
     }
@@ -196,7 +399,7 @@ another_key = 12345"#,
                 (Whitespace, " "),
                 (Equal, "="),
                 (Whitespace, " "),
-                (Number, "12345"),
+                (Integer, "12345"),
             ],
         )
     }
@@ -216,4 +419,160 @@ another_key = 12345"#,
             ]
         );
     }
+
+    #[test]
+    fn test_basic_string_escaped_quote() {
+        test_lexer(
+            r#"a = "a \" b" "\\""#,
+            &[
+                (Ident, "a"),
+                (Whitespace, " "),
+                (Equal, "="),
+                (Whitespace, " "),
+                (BasicString, r#""a \" b""#),
+                (Whitespace, " "),
+                (BasicString, r#""\\""#),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_literal_string() {
+        test_lexer(
+            r#"'C:\Users\' ''"#,
+            &[
+                (LiteralString, r#"'C:\Users\'"#),
+                (Whitespace, " "),
+                (LiteralString, "''"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_multi_line_strings() {
+        test_lexer(
+            "\"\"\"\none \"two\"\nthree\"\"\"\"\" '''\nit's\n''''' \"\"",
+            &[
+                (MultiLineBasicString, "\"\"\"\none \"two\"\nthree\"\"\"\"\""),
+                (Whitespace, " "),
+                (MultiLineLiteralString, "'''\nit's\n'''''"),
+                (Whitespace, " "),
+                (BasicString, "\"\""),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_unterminated_strings() {
+        test_lexer(
+            "\"abc\nx 'abc\r\n'''abc",
+            &[
+                (Error, "\"abc"),
+                (Newline, "\n"),
+                (Ident, "x"),
+                (Whitespace, " "),
+                (Error, "'abc"),
+                (Newline, "\r\n"),
+                (Error, "'''abc"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_integers() {
+        test_lexer(
+            "+99 -17 1_000 0xDEAD_beef 0o755 0b11",
+            &[
+                (Integer, "+99"),
+                (Whitespace, " "),
+                (Integer, "-17"),
+                (Whitespace, " "),
+                (Integer, "1_000"),
+                (Whitespace, " "),
+                (Integer, "0xDEAD_beef"),
+                (Whitespace, " "),
+                (Integer, "0o755"),
+                (Whitespace, " "),
+                (Integer, "0b11"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_floats() {
+        test_lexer(
+            "3.14 -0.01 5e+22 6.626e-34 1_0.0_1 inf -inf +nan",
+            &[
+                (Float, "3.14"),
+                (Whitespace, " "),
+                (Float, "-0.01"),
+                (Whitespace, " "),
+                (Float, "5e+22"),
+                (Whitespace, " "),
+                (Float, "6.626e-34"),
+                (Whitespace, " "),
+                (Float, "1_0.0_1"),
+                (Whitespace, " "),
+                (Float, "inf"),
+                (Whitespace, " "),
+                (Float, "-inf"),
+                (Whitespace, " "),
+                (Float, "+nan"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        test_lexer(
+            "0123 1__2 _1 1_ 1._5",
+            &[
+                (Integer, "0123"),
+                (Whitespace, " "),
+                (Integer, "1__2"),
+                (Whitespace, " "),
+                (Ident, "_1"),
+                (Whitespace, " "),
+                (Integer, "1_"),
+                (Whitespace, " "),
+                (Float, "1._5"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_number_like_keys() {
+        test_lexer(
+            "1st key1 -info - true2",
+            &[
+                (Ident, "1st"),
+                (Whitespace, " "),
+                (Ident, "key1"),
+                (Whitespace, " "),
+                (Ident, "-info"),
+                (Whitespace, " "),
+                (Ident, "-"),
+                (Whitespace, " "),
+                (Ident, "true2"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_date_times() {
+        test_lexer(
+            "1979-05-27T07:32:00Z 1979-05-27 00:32:00.999999-07:00 1979-05-27t07:32:00 1979-05-27,07:32:00.5",
+            &[
+                (OffsetDateTime, "1979-05-27T07:32:00Z"),
+                (Whitespace, " "),
+                (OffsetDateTime, "1979-05-27 00:32:00.999999-07:00"),
+                (Whitespace, " "),
+                (LocalDateTime, "1979-05-27t07:32:00"),
+                (Whitespace, " "),
+                (LocalDate, "1979-05-27"),
+                (Comma, ","),
+                (LocalTime, "07:32:00.5"),
+            ],
+        );
+    }
 }
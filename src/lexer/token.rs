@@ -10,21 +10,30 @@ pub enum SyntaxKind {
     TableHeader,
 
     Array,
-    Table,
+    InlineTable,
 
     Assign,
+    Key,
+    KeySegment,
 
     Ident,
     LBracket,
     RBracket,
     LBrace,
     RBrace,
-    Number,
+    Integer,
+    Float,
     True,
     False,
-    Date,
+    OffsetDateTime,
+    LocalDateTime,
+    LocalDate,
+    LocalTime,
     Equal,
-    String,
+    BasicString,
+    LiteralString,
+    MultiLineBasicString,
+    MultiLineLiteralString,
     Newline,
     Comma,
     Whitespace,
@@ -35,9 +44,54 @@ pub enum SyntaxKind {
 
 impl SyntaxKind {
     pub fn is_trivia(&self) -> bool {
+        matches!(self, Comment | Whitespace)
+    }
+
+    pub fn is_date_time(&self) -> bool {
+        matches!(self, OffsetDateTime | LocalDateTime | LocalDate | LocalTime)
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(
+            self,
+            BasicString | LiteralString | MultiLineBasicString | MultiLineLiteralString
+        )
+    }
+
+    /// How this kind is called in error messages.
+    pub fn description(&self) -> &'static str {
         match self {
-            Comment | Whitespace => true,
-            _ => false,
+            Root => "a document",
+            ArrayHeader => "an array of tables header",
+            TableHeader => "a table header",
+            Array => "an array",
+            InlineTable => "an inline table",
+            Assign => "a key/value pair",
+            Key | KeySegment => "a key",
+            Ident => "a bare key",
+            LBracket => "`[`",
+            RBracket => "`]`",
+            LBrace => "`{`",
+            RBrace => "`}`",
+            Integer => "an integer",
+            Float => "a float",
+            True => "`true`",
+            False => "`false`",
+            OffsetDateTime => "an offset date-time",
+            LocalDateTime => "a local date-time",
+            LocalDate => "a local date",
+            LocalTime => "a local time",
+            Equal => "`=`",
+            BasicString => "a basic string",
+            LiteralString => "a literal string",
+            MultiLineBasicString => "a multi-line basic string",
+            MultiLineLiteralString => "a multi-line literal string",
+            Newline => "a newline",
+            Comma => "`,`",
+            Whitespace => "whitespace",
+            Comment => "a comment",
+            Error => "invalid input",
+            Dot => "`.`",
         }
     }
 }
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use lsp_types::{Position, Range};
+use rowan::{TextRange, TextSize};
+use serde_json::Value;
+
+/// How the characters of a `Position` are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Picks the encoding from the `general.positionEncodings` the client offered in its
+    /// `initialize` params. Clients that do not offer any only understand UTF-16.
+    pub fn negotiate(initialize_params: &Value) -> PositionEncoding {
+        let offered = initialize_params
+            .pointer("/capabilities/general/positionEncodings")
+            .and_then(Value::as_array);
+        let offered = match offered {
+            Some(offered) => offered,
+            None => return PositionEncoding::Utf16,
+        };
+
+        // UTF-8 is what the text is stored as, so it is the cheapest to convert from.
+        [PositionEncoding::Utf8, PositionEncoding::Utf32]
+            .iter()
+            .copied()
+            .find(|encoding| offered.iter().any(|it| it.as_str() == Some(encoding.name())))
+            .unwrap_or(PositionEncoding::Utf16)
+    }
+
+    /// The name of the encoding in the protocol.
+    pub fn name(self) -> &'static str {
+        match self {
+            PositionEncoding::Utf8 => "utf-8",
+            PositionEncoding::Utf16 => "utf-16",
+            PositionEncoding::Utf32 => "utf-32",
+        }
+    }
+
+    fn len(self, c: char) -> u32 {
+        match self {
+            PositionEncoding::Utf8 => c.len_utf8() as u32,
+            PositionEncoding::Utf16 => c.len_utf16() as u32,
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+
+    /// How many units a character that is wider in UTF-8 takes in this encoding.
+    fn len_of(self, wide_char: &TextRange) -> u32 {
+        match (self, u32::from(wide_char.len())) {
+            (PositionEncoding::Utf16, 4) => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Converts between offsets into a text and the positions of the protocol. Built once per
+/// version of a document, after which every conversion only looks at a single line.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    encoding: PositionEncoding,
+    /// The contents of every line, without the line break.
+    lines: Vec<TextRange>,
+    /// The characters that are longer than a single unit in the encoding, for the lines that
+    /// have any. Their ranges are relative to the start of the line.
+    wide_chars: HashMap<u32, Vec<TextRange>>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str, encoding: PositionEncoding) -> LineIndex {
+        LineIndex::from_chunks(std::iter::once(text), encoding)
+    }
+
+    /// Indexes a text that is split into chunks, like the ones of a rope, without joining
+    /// them.
+    pub fn from_chunks<'a>(
+        chunks: impl Iterator<Item = &'a str>,
+        encoding: PositionEncoding,
+    ) -> LineIndex {
+        let mut lines = Vec::new();
+        let mut wide_chars = HashMap::new();
+        let mut line_start = TextSize::from(0);
+        let mut line_wide_chars = Vec::new();
+        let mut chunk_start = TextSize::from(0);
+        let mut after_cr = false;
+
+        for chunk in chunks {
+            for (i, c) in chunk.char_indices() {
+                let offset = chunk_start + TextSize::from(i as u32);
+                if c == '\n' {
+                    let end = if after_cr {
+                        offset - TextSize::of('\r')
+                    } else {
+                        offset
+                    };
+                    lines.push(TextRange::new(line_start, end));
+                    if !line_wide_chars.is_empty() {
+                        let line = (lines.len() - 1) as u32;
+                        wide_chars.insert(line, std::mem::take(&mut line_wide_chars));
+                    }
+                    line_start = offset + TextSize::of('\n');
+                } else if c.len_utf8() as u32 != encoding.len(c) {
+                    let start = offset - line_start;
+                    line_wide_chars.push(TextRange::at(start, TextSize::of(c)));
+                }
+                after_cr = c == '\r';
+            }
+            chunk_start += TextSize::of(chunk);
+        }
+        lines.push(TextRange::new(line_start, chunk_start));
+        if !line_wide_chars.is_empty() {
+            wide_chars.insert((lines.len() - 1) as u32, line_wide_chars);
+        }
+
+        LineIndex {
+            encoding,
+            lines,
+            wide_chars,
+        }
+    }
+
+    pub fn encoding(&self) -> PositionEncoding {
+        self.encoding
+    }
+
+    pub fn position(&self, offset: TextSize) -> Position {
+        let line = self
+            .lines
+            .partition_point(|range| range.start() <= offset)
+            .saturating_sub(1);
+        let column = offset - self.lines[line].start();
+
+        let mut character = u32::from(column);
+        for wide_char in self.wide_chars(line as u32) {
+            if wide_char.end() > column {
+                break;
+            }
+            character -= u32::from(wide_char.len()) - self.encoding.len_of(wide_char);
+        }
+        Position::new(line as u32, character)
+    }
+
+    /// The offset of a position. Positions past the end of a line or of the text are moved
+    /// back to it, as the protocol asks.
+    pub fn offset(&self, position: Position) -> TextSize {
+        let line = match self.lines.get(position.line as usize) {
+            Some(line) => *line,
+            None => return self.lines[self.lines.len() - 1].end(),
+        };
+
+        let mut column = TextSize::from(position.character);
+        for wide_char in self.wide_chars(position.line) {
+            if wide_char.start() >= column {
+                break;
+            }
+            column += wide_char.len() - TextSize::from(self.encoding.len_of(wide_char));
+        }
+        (line.start() + column).min(line.end())
+    }
+
+    /// The contents of a line, without the line break. Lines past the end of the text are
+    /// empty.
+    pub fn line(&self, line: u32) -> TextRange {
+        match self.lines.get(line as usize) {
+            Some(line) => *line,
+            None => TextRange::empty(self.lines[self.lines.len() - 1].end()),
+        }
+    }
+
+    pub fn range(&self, range: TextRange) -> Range {
+        Range::new(self.position(range.start()), self.position(range.end()))
+    }
+
+    pub fn text_range(&self, range: Range) -> TextRange {
+        let start = self.offset(range.start);
+        TextRange::new(start, self.offset(range.end).max(start))
+    }
+
+    fn wide_chars(&self, line: u32) -> &[TextRange] {
+        self.wide_chars.get(&line).map_or(&[], |it| it.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PositionEncoding::*;
+
+    fn check_round_trip(text: &str, encoding: PositionEncoding) {
+        let index = LineIndex::new(text, encoding);
+        for (i, _) in text.char_indices().chain(Some((text.len(), ' '))) {
+            let offset = TextSize::from(i as u32);
+            let position = index.position(offset);
+            if text[..i].ends_with('\r') && text[i..].starts_with('\n') {
+                continue;
+            }
+            assert_eq!(index.offset(position), offset, "{:?} at {}", encoding, i);
+        }
+    }
+
+    #[test]
+    fn positions() {
+        let text = "a = 1\nb = \"é😀\"\r\nc";
+        let utf16 = LineIndex::new(text, Utf16);
+        assert_eq!(utf16.position(0.into()), Position::new(0, 0));
+        assert_eq!(utf16.position(6.into()), Position::new(1, 0));
+        // The emoji starts after two bytes for the é, but after a single UTF-16 unit.
+        assert_eq!(utf16.position(13.into()), Position::new(1, 6));
+        assert_eq!(utf16.position(17.into()), Position::new(1, 8));
+        assert_eq!(utf16.position(20.into()), Position::new(2, 0));
+
+        let utf8 = LineIndex::new(text, Utf8);
+        assert_eq!(utf8.position(17.into()), Position::new(1, 11));
+
+        let utf32 = LineIndex::new(text, Utf32);
+        assert_eq!(utf32.position(17.into()), Position::new(1, 7));
+    }
+
+    #[test]
+    fn offsets_are_clamped() {
+        let index = LineIndex::new("ab\r\ncd", Utf16);
+        assert_eq!(index.offset(Position::new(0, 10)), 2.into());
+        assert_eq!(index.offset(Position::new(1, 10)), 6.into());
+        assert_eq!(index.offset(Position::new(5, 0)), 6.into());
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = "x = \"é😀\"\r\n# ♥ 𝄞\n\nend";
+        for &encoding in &[Utf8, Utf16, Utf32] {
+            check_round_trip(text, encoding);
+        }
+    }
+
+    #[test]
+    fn chunks() {
+        // Chunks can split a line break, but not a character.
+        let text = "a = 1\r\nb = \"😀\"\n";
+        let chunks = ["a = 1\r", "\nb = \"", "😀\"\n"];
+        let chunked = LineIndex::from_chunks(chunks.iter().copied(), Utf16);
+        let whole = LineIndex::new(text, Utf16);
+        for (i, _) in text.char_indices() {
+            let offset = TextSize::from(i as u32);
+            assert_eq!(chunked.position(offset), whole.position(offset), "at {}", i);
+        }
+        assert_eq!(chunked.lines, whole.lines);
+    }
+
+    #[test]
+    fn negotiation() {
+        let params = |encodings: Value| {
+            serde_json::json!({ "capabilities": { "general": { "positionEncodings": encodings } } })
+        };
+        assert_eq!(PositionEncoding::negotiate(&serde_json::json!({})), Utf16);
+        assert_eq!(
+            PositionEncoding::negotiate(&params(serde_json::json!(["utf-16", "utf-8"]))),
+            Utf8
+        );
+        assert_eq!(
+            PositionEncoding::negotiate(&params(serde_json::json!(["utf-32"]))),
+            Utf32
+        );
+    }
+}
@@ -0,0 +1,352 @@
+//! Logging of the language server: to a file, to stderr, and to the client through
+//! `window/logMessage`. What is logged is decided by a filter like
+//! `info,toml_lsp::parser=trace`, which gives a level for all targets and for some modules.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use lsp_server::Notification;
+use lsp_types::{LogMessageParams, MessageType};
+use serde_json::Value;
+
+/// Where to log to and what. Every source of settings fills in what it knows, and the
+/// sources are then combined with [`LogConfig::or`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogConfig {
+    pub level: Option<String>,
+    pub file: Option<PathBuf>,
+    pub stderr: Option<bool>,
+}
+
+impl LogConfig {
+    /// Reads `TOML_LSP_LOG_LEVEL`, `TOML_LSP_LOG_FILE` and `TOML_LSP_LOG_STDERR`.
+    pub fn from_env() -> LogConfig {
+        let var = |name| std::env::var(name).ok().filter(|it| !it.is_empty());
+        LogConfig {
+            level: var("TOML_LSP_LOG_LEVEL"),
+            file: var("TOML_LSP_LOG_FILE").map(PathBuf::from),
+            stderr: var("TOML_LSP_LOG_STDERR").map(|it| it == "1" || it == "true"),
+        }
+    }
+
+    /// Reads the `log` object of the `initializationOptions`, with the fields `level`,
+    /// `file` and `stderr`.
+    pub fn from_initialization_options(options: Option<&Value>) -> LogConfig {
+        let log = options.and_then(|options| options.get("log"));
+        let field = |name| log.and_then(|log| log.get(name));
+        LogConfig {
+            level: field("level").and_then(Value::as_str).map(String::from),
+            file: field("file").and_then(Value::as_str).map(PathBuf::from),
+            stderr: field("stderr").and_then(Value::as_bool),
+        }
+    }
+
+    /// Takes the settings that `self` leaves open from `other`.
+    pub fn or(self, other: LogConfig) -> LogConfig {
+        LogConfig {
+            level: self.level.or(other.level),
+            file: self.file.or(other.file),
+            stderr: self.stderr.or(other.stderr),
+        }
+    }
+}
+
+/// The log file to use if none is configured.
+fn default_file() -> Option<PathBuf> {
+    Some(
+        dirs_next::data_dir()?
+            .join("test_lsp_server")
+            .join("lsp_server.log"),
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    default: LevelFilter,
+    /// Levels for module paths, the most specific last.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    pub fn parse(filter: &str) -> Result<Filter, String> {
+        let level = |level: &str| {
+            level
+                .trim()
+                .parse::<LevelFilter>()
+                .map_err(|_| format!("Invalid log level `{}`", level.trim()))
+        };
+
+        let mut default = LevelFilter::Info;
+        let mut modules = Vec::new();
+        for directive in filter.split(',').filter(|it| !it.trim().is_empty()) {
+            match directive.split_once('=') {
+                Some((module, filter)) => modules.push((module.trim().to_string(), level(filter)?)),
+                None => default = level(directive)?,
+            }
+        }
+        modules.sort_by_key(|(module, _)| module.len());
+        Ok(Filter { default, modules })
+    }
+
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .rev()
+            .find(|(module, _)| {
+                target == module
+                    || target.starts_with(module.as_str())
+                        && target[module.len()..].starts_with("::")
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        let modules = self.modules.iter().map(|(_, level)| *level);
+        modules.fold(self.default, std::cmp::max)
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter {
+            default: LevelFilter::Info,
+            modules: Vec::new(),
+        }
+    }
+}
+
+type Forward = Arc<dyn Fn(Notification) + Send + Sync>;
+
+struct State {
+    filter: Filter,
+    /// The log file, if it could be opened.
+    file: Option<File>,
+    path: Option<PathBuf>,
+    stderr: bool,
+    client: Option<Forward>,
+}
+
+/// A handle to the installed logger, to change its settings later on.
+#[derive(Clone)]
+pub struct Logger {
+    state: Arc<Mutex<State>>,
+}
+
+impl Logger {
+    /// Installs the logger for the whole process. Never fails: settings that cannot be
+    /// used, like a log file on a read-only filesystem, are reported on stderr and skipped.
+    pub fn install(config: &LogConfig) -> Logger {
+        let logger = Logger {
+            state: Arc::new(Mutex::new(State {
+                filter: Filter::default(),
+                file: None,
+                path: None,
+                stderr: false,
+                client: None,
+            })),
+        };
+        if log::set_logger(Box::leak(Box::new(logger.clone()))).is_err() {
+            eprintln!("warning: a logger is already installed");
+        }
+        logger.configure(config);
+        logger
+    }
+
+    pub fn configure(&self, config: &LogConfig) {
+        let filter = match config.level.as_deref().map(Filter::parse) {
+            Some(Ok(filter)) => filter,
+            Some(Err(err)) => {
+                eprintln!("warning: {}", err);
+                Filter::default()
+            }
+            None => Filter::default(),
+        };
+        let path = config.file.clone().or_else(default_file);
+
+        log::set_max_level(filter.max_level());
+        let mut state = self.state.lock().unwrap();
+        state.filter = filter;
+        state.stderr = config.stderr.unwrap_or(false);
+        if path != state.path {
+            state.file = path.as_deref().and_then(|path| match open(path) {
+                Ok(file) => Some(file),
+                Err(err) => {
+                    eprintln!(
+                        "warning: cannot log to {}, logging to it is disabled: {}",
+                        path.display(),
+                        err
+                    );
+                    None
+                }
+            });
+            state.path = path;
+        }
+    }
+
+    /// Sends every message that is logged from now on to the client as well.
+    pub fn forward<F: Fn(Notification) + Send + Sync + 'static>(&self, send: F) {
+        self.state.lock().unwrap().client = Some(Arc::new(send));
+    }
+
+    /// Stops sending messages to the client, which lets go of the connection to it.
+    pub fn stop_forwarding(&self) {
+        self.state.lock().unwrap().client = None;
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match self.state.lock() {
+            Ok(state) => metadata.level() <= state.filter.level(metadata.target()),
+            Err(_) => false,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        // A panic while logging must not take the logger down with it.
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if record.level() > state.filter.level(record.target()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} [{}] {}",
+            timestamp(SystemTime::now()),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        if let Some(file) = &mut state.file {
+            let _ = writeln!(file, "{}", line);
+        }
+        if state.stderr {
+            eprintln!("{}", line);
+        }
+        // Messages to the client are logged by `lsp_server` as they are sent, so forwarding
+        // its records would never end.
+        let forward = !record.target().starts_with("lsp_server");
+        let client = state.client.clone().filter(|_| forward);
+        // Sending waits for the thread that writes to the client, which logs as well.
+        drop(state);
+        if let Some(client) = client {
+            let typ = match record.level() {
+                Level::Error => MessageType::Error,
+                Level::Warn => MessageType::Warning,
+                Level::Info => MessageType::Info,
+                Level::Debug | Level::Trace => MessageType::Log,
+            };
+            client(Notification::new(
+                "window/logMessage".into(),
+                LogMessageParams {
+                    typ,
+                    message: format!("[{}] {}", record.target(), record.args()),
+                },
+            ));
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(file) = &mut state.file {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Formats a time as UTC, like `2021-01-30T12:05:09.123Z`.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = ((secs / 86400) as i64, secs % 86400);
+
+    // Converts days since the epoch to a date in the proleptic Gregorian calendar.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn filters() {
+        let filter = Filter::parse("warn, toml_lsp::parser=trace,toml_lsp=info").unwrap();
+        assert_eq!(filter.level("other"), LevelFilter::Warn);
+        assert_eq!(filter.level("toml_lsp"), LevelFilter::Info);
+        assert_eq!(
+            filter.level("toml_lsp::parser::reparse"),
+            LevelFilter::Trace
+        );
+        assert_eq!(filter.level("toml_lsp::parsers"), LevelFilter::Info);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+
+        assert_eq!(Filter::parse("").unwrap(), Filter::default());
+        assert_eq!(
+            Filter::parse("a=loud"),
+            Err("Invalid log level `loud`".to_string())
+        );
+    }
+
+    #[test]
+    fn sources() {
+        let options = serde_json::json!({ "log": { "level": "debug", "stderr": true } });
+        let flags = LogConfig {
+            level: Some("trace".into()),
+            ..LogConfig::default()
+        };
+        let config = flags.or(LogConfig::from_initialization_options(Some(&options)));
+        assert_eq!(
+            config,
+            LogConfig {
+                level: Some("trace".into()),
+                file: None,
+                stderr: Some(true),
+            }
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_612_008_309_123);
+        assert_eq!(timestamp(time), "2021-01-30T12:05:09.123Z");
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+}
@@ -1,12 +1,16 @@
-use std::fs::{self, File};
 use std::io;
-use std::{collections::HashMap, panic};
+use std::{collections::HashMap, env, panic, path::PathBuf, process, sync::Arc};
 
+use document::Document;
+use formatter::FormatOptions;
+use line_index::PositionEncoding;
+use logging::{LogConfig, Logger};
+use schema::{Schema, SchemaConfig};
 use log::{debug, error, info, trace, warn};
 use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
 use lsp_types::*;
 use lsp_types::{
-    notification::{DidChangeTextDocument, DidOpenTextDocument},
+    notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument},
     Diagnostic, PublishDiagnosticsParams, Url,
 };
 use lsp_types::{
@@ -14,37 +18,43 @@ use lsp_types::{
     request::{Request as RequestTrait, *},
     *,
 };
-use parser::AST;
-use rowan::TextRange;
-use simplelog::WriteLogger;
 
+mod cli;
+mod completion;
+mod diagnostics;
+mod document;
+mod folding;
+mod formatter;
+mod hover;
 mod lexer;
+mod line_index;
+mod logging;
 mod parser;
-
-use parser::ParseError;
+mod schema;
+mod selection;
+mod semantic;
+mod semantic_tokens;
+mod symbols;
 
 type DynResult<T, E = Box<dyn std::error::Error>> = Result<T, E>;
 
 fn main() {
-    run().unwrap()
-}
-
-fn run() -> DynResult<()> {
-    let data_dir = dirs_next::data_dir()
-        .expect("Failed to find data_dir")
-        .join("test_lsp_server");
-    let file_path = data_dir.join("lsp_server.log");
-
-    if !data_dir.exists() {
-        fs::create_dir_all(data_dir).expect("Failed to create data dir");
+    let command = match cli::Command::parse(env::args().skip(1)) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    match command {
+        cli::Command::Lsp { log } => run_lsp(log).unwrap(),
+        command => process::exit(cli::run(command)),
     }
+}
 
-    WriteLogger::init(
-        simplelog::LevelFilter::Trace,
-        simplelog::Config::default(),
-        File::create(file_path).expect("Failed to create log file"),
-    )
-    .expect("Failed to start logger");
+fn run_lsp(flags: LogConfig) -> DynResult<()> {
+    let process_config = flags.or(LogConfig::from_env());
+    let logger = Logger::install(&process_config);
 
     panic::set_hook(Box::new(move |panic| {
         error!("----- Panic -----");
@@ -52,28 +62,90 @@ fn run() -> DynResult<()> {
     }));
 
     let (connection, io_threads) = Connection::stdio();
-    let capabilities = serde_json::to_value(&ServerCapabilities {
-        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::Full)),
+    let (id, params) = connection.initialize_start()?;
+    logger.configure(&process_config.or(LogConfig::from_initialization_options(
+        params.get("initializationOptions"),
+    )));
+    let sender = connection.sender.clone();
+    logger.forward(move |notification| {
+        // Fails only once the client is gone, when there is no one left to tell.
+        let _ = sender.send(Message::Notification(notification));
+    });
+    let workspace = params
+        .get("rootUri")
+        .and_then(|uri| serde_json::from_value::<Url>(uri.clone()).ok())
+        .and_then(|uri| uri.to_file_path().ok());
+    let schema_config = SchemaConfig::from_settings(params.get("initializationOptions"));
+    let encoding = PositionEncoding::negotiate(&params);
+    info!("position encoding: {}", encoding.name());
+
+    let mut capabilities = serde_json::to_value(&ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::Incremental,
+        )),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".into(), "[".into(), "=".into()]),
+            ..CompletionOptions::default()
+        }),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: semantic_tokens::legend(),
+                range: Some(true),
+                full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                ..SemanticTokensOptions::default()
+            },
+        )),
         ..ServerCapabilities::default()
     })
     .unwrap();
+    // `positionEncoding` is newer than our version of lsp-types.
+    capabilities["positionEncoding"] = encoding.name().into();
 
-    connection.initialize(capabilities)?;
+    connection.initialize_finish(id, serde_json::json!({ "capabilities": capabilities }))?;
 
     Server {
         files: HashMap::new(),
+        semantic_tokens: HashMap::new(),
+        next_result_id: 0,
+        workspace,
+        schema_config,
+        schemas: schema::Store::default(),
         conn: connection,
+        encoding,
     }
     .run();
 
+    // The connection only closes once every handle to it is gone.
+    logger.stop_forwarding();
     io_threads.join()?;
 
     Ok(())
 }
 
 struct Server {
-    files: HashMap<Url, (AST, String)>,
+    files: HashMap<Url, Document>,
+    /// The last semantic tokens sent for each document, with their result id, which
+    /// `semanticTokens/full/delta` requests refer to.
+    semantic_tokens: HashMap<Url, (String, Vec<SemanticToken>)>,
+    next_result_id: u64,
+    workspace: Option<PathBuf>,
+    schema_config: SchemaConfig,
+    schemas: schema::Store,
     conn: Connection,
+    encoding: PositionEncoding,
+}
+
+fn text_edits(document: &Document, edits: Vec<parser::TextEdit>) -> Vec<TextEdit> {
+    edits
+        .into_iter()
+        .map(|edit| TextEdit::new(document.line_index().range(edit.delete), edit.insert))
+        .collect()
 }
 
 impl Server {
@@ -114,20 +186,43 @@ impl Server {
             DidOpenTextDocument::METHOD => {
                 info!("did open");
                 let params: DidOpenTextDocumentParams = serde_json::from_value(req.params)?;
-                let text = params.text_document.text;
-                let parsed = parser::parse(&text);
-                self.send_diagnostics(params.text_document.uri.clone(), &text, &parsed)?;
-                self.files.insert(params.text_document.uri, (parsed, text));
+                let document = Document::new(
+                    &params.text_document.text,
+                    params.text_document.version,
+                    self.encoding,
+                );
+                let uri = params.text_document.uri;
+                self.files.insert(uri.clone(), document);
+                self.send_diagnostics(uri)?;
             }
             DidChangeTextDocument::METHOD => {
                 let params: DidChangeTextDocumentParams = serde_json::from_value(req.params)?;
-                if let Some(change) = params.content_changes.into_iter().last() {
-                    let parsed = parser::parse(&change.text);
-                    self.send_diagnostics(params.text_document.uri.clone(), &change.text, &parsed)?;
-                    self.files
-                        .insert(params.text_document.uri, (parsed, change.text));
+                let uri = params.text_document.uri;
+                let document = match self.files.get_mut(&uri) {
+                    Some(document) => document,
+                    None => {
+                        warn!("change to a document that is not open: {}", uri);
+                        return Ok(());
+                    }
+                };
+                document.apply_changes(&params.content_changes, params.text_document.version);
+                self.send_diagnostics(uri)?;
+            }
+            DidChangeConfiguration::METHOD => {
+                let params: DidChangeConfigurationParams = serde_json::from_value(req.params)?;
+                // Clients send the settings of the server either as they are or in a section.
+                let settings = params.settings.get("toml").unwrap_or(&params.settings);
+                self.schema_config = SchemaConfig::from_settings(Some(settings));
+                let uris: Vec<_> = self.files.keys().cloned().collect();
+                for uri in uris {
+                    self.send_diagnostics(uri)?;
                 }
             }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(req.params)?;
+                self.files.remove(&params.text_document.uri);
+                self.semantic_tokens.remove(&params.text_document.uri);
+            }
             _ => (),
         }
         Ok(())
@@ -153,31 +248,48 @@ impl Server {
         Ok(())
     }
 
-    fn send_diagnostics(&mut self, uri: Url, code: &str, ast: &AST) -> DynResult<()> {
+    fn send_diagnostics(&mut self, uri: Url) -> DynResult<()> {
         info!("sending diagnostics");
-        let errors = ast.errors();
-        let mut diagnostics = Vec::with_capacity(errors.len());
-        for err in errors {
-            if let ParseError::Expected {
-                range: Some(node_range), ..
-            } = err
-            {
-                let the_range = range(code, node_range);
-                debug!("range: {:?}", the_range);
-                diagnostics.push(Diagnostic {
-                    range: the_range,
-                    severity: Some(DiagnosticSeverity::Error),
-                    message: err.to_string(),
-                    ..Diagnostic::default()
-                });
-            }
+        let document = self
+            .files
+            .get(&uri)
+            .ok_or_else(|| format!("Document is not open: {}", uri))?;
+        let line_index = document.line_index();
+        let mut diagnostics = diagnostics::diagnostics(document.ast());
+        if let Some(source) = self.schema_source(&uri, document) {
+            diagnostics.extend(diagnostics::schema_diagnostics(
+                document.ast(),
+                &source,
+                &mut self.schemas,
+            ));
         }
+        let diagnostics = diagnostics
+            .into_iter()
+            .map(|diagnostic| Diagnostic {
+                range: line_index.range(diagnostic.range),
+                severity: Some(DiagnosticSeverity::Error),
+                code: Some(NumberOrString::String(diagnostic.code.into())),
+                source: Some("toml-lsp".into()),
+                message: diagnostic.message,
+                related_information: diagnostic.first_definition.map(|first| {
+                    vec![DiagnosticRelatedInformation {
+                        location: Location {
+                            uri: uri.clone(),
+                            range: line_index.range(first),
+                        },
+                        message: "first defined here".into(),
+                    }]
+                }),
+                ..Diagnostic::default()
+            })
+            .collect();
+        let version = Some(document.version());
         self.notify(Notification::new(
             "textDocument/publishDiagnostics".into(),
             PublishDiagnosticsParams {
                 uri,
                 diagnostics,
-                version: None,
+                version,
             },
         ));
         Ok(())
@@ -185,9 +297,169 @@ impl Server {
 
     fn handle_request(&mut self, req: Request) -> DynResult<()> {
         trace!("Handling request {:#?}", req);
+        match &*req.method {
+            DocumentSymbolRequest::METHOD => {
+                let params: DocumentSymbolParams = serde_json::from_value(req.params)?;
+                let document = self.document(&params.text_document.uri)?;
+                let symbols = symbols::document_symbols(document.ast(), document.line_index());
+                let result = DocumentSymbolResponse::Nested(symbols);
+                self.reply(Response::new_ok(req.id, result));
+            }
+            FoldingRangeRequest::METHOD => {
+                let params: FoldingRangeParams = serde_json::from_value(req.params)?;
+                let document = self.document(&params.text_document.uri)?;
+                let result = folding::folding_ranges(document.ast(), document.line_index());
+                self.reply(Response::new_ok(req.id, result));
+            }
+            HoverRequest::METHOD => {
+                let params: HoverParams = serde_json::from_value(req.params)?;
+                let position = params.text_document_position_params;
+                let schema = self.schema(&position.text_document.uri);
+                let document = self.document(&position.text_document.uri)?;
+                let ast = document.ast();
+                let line_index = document.line_index();
+                let semantic = semantic::lower(&ast.root());
+                let offset = line_index.offset(position.position);
+                let result = hover::hover(ast, &semantic, schema.as_deref(), line_index, offset);
+                self.reply(Response::new_ok(req.id, result));
+            }
+            SelectionRangeRequest::METHOD => {
+                let params: SelectionRangeParams = serde_json::from_value(req.params)?;
+                let document = self.document(&params.text_document.uri)?;
+                let line_index = document.line_index();
+                let result: Vec<_> = params
+                    .positions
+                    .into_iter()
+                    .map(|position| {
+                        let offset = line_index.offset(position);
+                        let ranges = selection::selection_ranges(document.ast(), offset);
+                        selection::to_lsp(&ranges, line_index)
+                    })
+                    .collect();
+                self.reply(Response::new_ok(req.id, result));
+            }
+            Completion::METHOD => {
+                let params: CompletionParams = serde_json::from_value(req.params)?;
+                let position = params.text_document_position;
+                let uri = &position.text_document.uri;
+                let schema = self.schema(uri);
+                let document = self.document(uri)?;
+                let result = schema.map(|schema| {
+                    let ast = document.ast();
+                    let semantic = semantic::lower(&ast.root());
+                    let offset = document.line_index().offset(position.position);
+                    completion::completions(ast, &semantic, &schema, offset)
+                });
+                self.reply(Response::new_ok(req.id, result));
+            }
+            Formatting::METHOD => {
+                let params: DocumentFormattingParams = serde_json::from_value(req.params)?;
+                let document = self.document(&params.text_document.uri)?;
+                let options = FormatOptions::from_lsp(&params.options);
+                let result = formatter::format_edits(document.ast(), &options, None)
+                    .map(|edits| text_edits(document, edits));
+                self.reply(Response::new_ok(req.id, result));
+            }
+            RangeFormatting::METHOD => {
+                let params: DocumentRangeFormattingParams = serde_json::from_value(req.params)?;
+                let document = self.document(&params.text_document.uri)?;
+                let options = FormatOptions::from_lsp(&params.options);
+                let range = document.line_index().text_range(params.range);
+                let result = formatter::format_edits(document.ast(), &options, Some(range))
+                    .map(|edits| text_edits(document, edits));
+                self.reply(Response::new_ok(req.id, result));
+            }
+            SemanticTokensFullRequest::METHOD => {
+                let params: SemanticTokensParams = serde_json::from_value(req.params)?;
+                let uri = params.text_document.uri;
+                let document = self.document(&uri)?;
+                let data =
+                    semantic_tokens::semantic_tokens(document.ast(), document.line_index(), None);
+                let result_id = self.remember_tokens(uri, data.clone());
+                let result = SemanticTokensResult::Tokens(SemanticTokens {
+                    result_id: Some(result_id),
+                    data,
+                });
+                self.reply(Response::new_ok(req.id, result));
+            }
+            SemanticTokensFullDeltaRequest::METHOD => {
+                let params: SemanticTokensDeltaParams = serde_json::from_value(req.params)?;
+                let uri = params.text_document.uri;
+                let document = self.document(&uri)?;
+                let data =
+                    semantic_tokens::semantic_tokens(document.ast(), document.line_index(), None);
+                let edits = match self.semantic_tokens.get(&uri) {
+                    Some((id, old)) if *id == params.previous_result_id => {
+                        Some(semantic_tokens::diff(old, &data))
+                    }
+                    _ => None,
+                };
+                let result_id = self.remember_tokens(uri, data.clone());
+                let result = match edits {
+                    Some(edits) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                        result_id: Some(result_id),
+                        edits,
+                    }),
+                    // The client refers to tokens that are gone, so it gets all of them.
+                    None => SemanticTokensFullDeltaResult::Tokens(SemanticTokens {
+                        result_id: Some(result_id),
+                        data,
+                    }),
+                };
+                self.reply(Response::new_ok(req.id, result));
+            }
+            SemanticTokensRangeRequest::METHOD => {
+                let params: SemanticTokensRangeParams = serde_json::from_value(req.params)?;
+                let document = self.document(&params.text_document.uri)?;
+                let line_index = document.line_index();
+                let range = line_index.text_range(params.range);
+                let data = semantic_tokens::semantic_tokens(document.ast(), line_index, Some(range));
+                let result = SemanticTokensRangeResult::Tokens(SemanticTokens {
+                    result_id: None,
+                    data,
+                });
+                self.reply(Response::new_ok(req.id, result));
+            }
+            _ => self.reply(Response::new_err(
+                req.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request {}", req.method),
+            )),
+        }
         Ok(())
     }
 
+    fn schema_source(&self, uri: &Url, document: &Document) -> Option<schema::Source> {
+        let path = uri.to_file_path().ok()?;
+        schema::locate(
+            document.ast(),
+            &path,
+            self.workspace.as_deref(),
+            &self.schema_config,
+        )
+    }
+
+    /// The schema of a document, if it has one that can be loaded.
+    fn schema(&mut self, uri: &Url) -> Option<Arc<Schema>> {
+        let document = self.files.get(uri)?;
+        let source = self.schema_source(uri, document)?;
+        self.schemas.get(&source.path).ok()
+    }
+
+    fn document(&self, uri: &Url) -> DynResult<&Document> {
+        self.files
+            .get(uri)
+            .ok_or_else(|| format!("Document is not open: {}", uri).into())
+    }
+
+    /// Keeps the tokens of a document for later delta requests, and returns their result id.
+    fn remember_tokens(&mut self, uri: Url, tokens: Vec<SemanticToken>) -> String {
+        self.next_result_id += 1;
+        let result_id = self.next_result_id.to_string();
+        self.semantic_tokens.insert(uri, (result_id.clone(), tokens));
+        result_id
+    }
+
     fn reply(&mut self, response: Response) {
         trace!("Sending response: {:#?}", response);
         self.conn.sender.send(Message::Response(response)).unwrap();
@@ -213,30 +485,3 @@ impl Server {
         ));
     }
 }
-
-pub fn offset_to_pos(code: &str, offset: usize) -> Position {
-    debug!("Code: {:?}", code);
-    let start_of_line = code[..offset].rfind('\n').map_or(0, |n| n + 1);
-    let line = code[..start_of_line].chars().filter(|&c| c == '\n').count() as u32;
-    let character = code[start_of_line..offset] 
-            .chars()
-            .map(|c| c.len_utf16() as u32)
-            .sum();
-
-    debug!("Start of line: {:?}", start_of_line);
-    debug!("Line number: {:?}", start_of_line);
-    debug!("Character number: {:?}", start_of_line);
-
-    Position {
-        character,
-        line
-    }
-}
-
-pub fn range(code: &str, range: TextRange) -> Range {
-    debug!("Text range: {:#?}", range);
-    Range {
-        start: offset_to_pos(code, range.start().into()),
-        end: offset_to_pos(code, range.end().into()),
-    }
-}
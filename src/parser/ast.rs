@@ -0,0 +1,270 @@
+//! Typed wrappers over the syntax tree, so that features don't have to walk `SyntaxKind`s
+//! by hand. Every accessor returns `None` or skips children that are missing because of
+//! syntax errors.
+
+use rowan::{NodeOrToken, TextRange};
+
+use super::{string_value, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+
+pub trait AstNode: Sized {
+    fn cast(node: SyntaxNode) -> Option<Self>;
+
+    fn syntax(&self) -> &SyntaxNode;
+
+    fn text_range(&self) -> TextRange {
+        self.syntax().text_range()
+    }
+}
+
+macro_rules! ast_node {
+    ($(#[$meta:meta])* $name:ident, $kind:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub struct $name(SyntaxNode);
+
+        impl AstNode for $name {
+            fn cast(node: SyntaxNode) -> Option<Self> {
+                if node.kind() == SyntaxKind::$kind {
+                    Some(Self(node))
+                } else {
+                    None
+                }
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
+        }
+    };
+}
+
+ast_node!(
+    /// The whole document.
+    Root, Root
+);
+ast_node!(
+    /// A `[table]` header together with the key/value pairs below it.
+    TableHeader, TableHeader
+);
+ast_node!(
+    /// An `[[array.of.tables]]` header together with the key/value pairs below it.
+    ArrayHeader, ArrayHeader
+);
+ast_node!(KeyValue, Assign);
+ast_node!(
+    /// A possibly dotted key, like `a."b".c`.
+    Key, Key
+);
+ast_node!(KeySegment, KeySegment);
+ast_node!(Array, Array);
+ast_node!(InlineTable, InlineTable);
+
+fn child<N: AstNode>(node: &SyntaxNode) -> Option<N> {
+    node.children().find_map(N::cast)
+}
+
+fn children<N: AstNode>(node: &SyntaxNode) -> impl Iterator<Item = N> {
+    node.children().filter_map(N::cast)
+}
+
+/// Something that can appear directly in the document.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Entry {
+    KeyValue(KeyValue),
+    Table(TableHeader),
+    ArrayOfTables(ArrayHeader),
+}
+
+impl Entry {
+    pub fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Entry::KeyValue(it) => it.syntax(),
+            Entry::Table(it) => it.syntax(),
+            Entry::ArrayOfTables(it) => it.syntax(),
+        }
+    }
+}
+
+impl Root {
+    /// The key/value pairs before the first header, followed by the headers, in document
+    /// order.
+    pub fn entries(&self) -> impl Iterator<Item = Entry> {
+        self.0.children().filter_map(|node| {
+            KeyValue::cast(node.clone())
+                .map(Entry::KeyValue)
+                .or_else(|| TableHeader::cast(node.clone()).map(Entry::Table))
+                .or_else(|| ArrayHeader::cast(node).map(Entry::ArrayOfTables))
+        })
+    }
+}
+
+impl TableHeader {
+    pub fn key(&self) -> Option<Key> {
+        child(&self.0)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = KeyValue> {
+        children(&self.0)
+    }
+}
+
+impl ArrayHeader {
+    pub fn key(&self) -> Option<Key> {
+        child(&self.0)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = KeyValue> {
+        children(&self.0)
+    }
+}
+
+impl KeyValue {
+    pub fn key(&self) -> Option<Key> {
+        child(&self.0)
+    }
+
+    pub fn value(&self) -> Option<Value> {
+        self.0
+            .children_with_tokens()
+            .skip_while(|it| it.kind() != SyntaxKind::Equal)
+            .find_map(Value::cast)
+    }
+}
+
+impl Key {
+    pub fn segments(&self) -> impl Iterator<Item = KeySegment> {
+        children(&self.0)
+    }
+}
+
+impl KeySegment {
+    pub fn token(&self) -> Option<SyntaxToken> {
+        self.0.first_token()
+    }
+
+    /// The name this segment stands for, with quotes removed and escapes resolved.
+    pub fn name(&self) -> Option<String> {
+        let token = self.token()?;
+        let text: &str = token.text();
+        if token.kind().is_string() {
+            Some(string_value(
+                token.kind(),
+                text,
+                token.text_range().start(),
+                |_| (),
+            ))
+        } else {
+            Some(text.to_string())
+        }
+    }
+}
+
+impl Array {
+    pub fn values(&self) -> impl Iterator<Item = Value> {
+        self.0.children_with_tokens().filter_map(Value::cast)
+    }
+}
+
+impl InlineTable {
+    pub fn entries(&self) -> impl Iterator<Item = KeyValue> {
+        children(&self.0)
+    }
+}
+
+/// The right hand side of a key/value pair, or an element of an array.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Value {
+    Array(Array),
+    InlineTable(InlineTable),
+    /// A string, number, boolean or date-time token.
+    Scalar(SyntaxToken),
+}
+
+impl Value {
+    pub fn cast(element: SyntaxElement) -> Option<Value> {
+        match element {
+            NodeOrToken::Node(node) => Array::cast(node.clone())
+                .map(Value::Array)
+                .or_else(|| InlineTable::cast(node).map(Value::InlineTable)),
+            NodeOrToken::Token(token) if is_scalar(token.kind()) => Some(Value::Scalar(token)),
+            NodeOrToken::Token(_) => None,
+        }
+    }
+
+    pub fn syntax(&self) -> SyntaxElement {
+        match self {
+            Value::Array(it) => it.syntax().clone().into(),
+            Value::InlineTable(it) => it.syntax().clone().into(),
+            Value::Scalar(it) => it.clone().into(),
+        }
+    }
+
+    pub fn text_range(&self) -> TextRange {
+        match self {
+            Value::Array(it) => it.text_range(),
+            Value::InlineTable(it) => it.text_range(),
+            Value::Scalar(it) => it.text_range(),
+        }
+    }
+}
+
+fn is_scalar(kind: SyntaxKind) -> bool {
+    use SyntaxKind::*;
+
+    kind.is_string() || kind.is_date_time() || matches!(kind, Integer | Float | True | False)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn keys(key: Option<Key>) -> Vec<String> {
+        key.unwrap()
+            .segments()
+            .map(|segment| segment.name().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn entries() {
+        let root = parse("a.'b c' = 1\n[table]\nx = [1, { y = true }]\n[[array]]\n").root();
+        let entries: Vec<_> = root.entries().collect();
+        assert_eq!(entries.len(), 3);
+
+        let kv = match &entries[0] {
+            Entry::KeyValue(kv) => kv,
+            entry => panic!("unexpected entry {:?}", entry),
+        };
+        assert_eq!(keys(kv.key()), ["a", "b c"]);
+        assert!(matches!(kv.value(), Some(Value::Scalar(t)) if t.text() == "1"));
+
+        let table = match &entries[1] {
+            Entry::Table(table) => table,
+            entry => panic!("unexpected entry {:?}", entry),
+        };
+        assert_eq!(keys(table.key()), ["table"]);
+        let x = table.entries().next().unwrap();
+        let values: Vec<_> = match x.value() {
+            Some(Value::Array(array)) => array.values().collect(),
+            value => panic!("unexpected value {:?}", value),
+        };
+        assert_eq!(values.len(), 2);
+        let inline = match &values[1] {
+            Value::InlineTable(inline) => inline,
+            value => panic!("unexpected value {:?}", value),
+        };
+        assert_eq!(keys(inline.entries().next().unwrap().key()), ["y"]);
+
+        assert!(matches!(&entries[2], Entry::ArrayOfTables(header) if keys(header.key()) == ["array"]));
+    }
+
+    #[test]
+    fn missing_value() {
+        let root = parse("a = \n").root();
+        match root.entries().next() {
+            Some(Entry::KeyValue(kv)) => assert_eq!(kv.value(), None),
+            entry => panic!("unexpected entry {:?}", entry),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+/// Checks that the fields of a date-time token are in range, since the lexer only checks
+/// its shape. Returns the reason it is invalid otherwise.
+pub fn validate_date_time(text: &str) -> Result<(), &'static str> {
+    let (date, time) = if text.as_bytes().get(4) == Some(&b'-') {
+        match text.get(10..) {
+            Some("") | None => (text, None),
+            Some(_) => (&text[..10], Some(&text[11..])),
+        }
+    } else {
+        ("", Some(text))
+    };
+
+    if !date.is_empty() {
+        validate_date(date)?;
+    }
+    if let Some(time) = time {
+        validate_time(time)?;
+    }
+    Ok(())
+}
+
+fn validate_date(date: &str) -> Result<(), &'static str> {
+    let year = field(date, 0, 4);
+    let month = field(date, 5, 7);
+    let day = field(date, 8, 10);
+
+    if !(1..=12).contains(&month) {
+        return Err("month must be between 01 and 12");
+    }
+    if day < 1 || day > days_in_month(year, month) {
+        return Err("day is out of range for the month");
+    }
+    Ok(())
+}
+
+fn validate_time(time: &str) -> Result<(), &'static str> {
+    if field(time, 0, 2) > 23 {
+        return Err("hour must be between 00 and 23");
+    }
+    if field(time, 3, 5) > 59 {
+        return Err("minute must be between 00 and 59");
+    }
+    if time.as_bytes().get(5) != Some(&b':') {
+        return Err("seconds are required");
+    }
+    // 60 is allowed for leap seconds.
+    if field(time, 6, 8) > 60 {
+        return Err("second must be between 00 and 60");
+    }
+
+    let offset = time[8..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    if offset.len() == 6 {
+        if field(offset, 1, 3) > 23 {
+            return Err("offset hour must be between 00 and 23");
+        }
+        if field(offset, 4, 6) > 59 {
+            return Err("offset minute must be between 00 and 59");
+        }
+    }
+    Ok(())
+}
+
+fn field(text: &str, start: usize, end: usize) -> u32 {
+    text[start..end]
+        .parse()
+        .expect("BUG: the lexer only accepts digits here")
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid() {
+        for text in &[
+            "1979-05-27T07:32:00Z",
+            "1979-05-27T00:32:00.999999-07:00",
+            "1979-05-27 07:32:00",
+            "2000-02-29",
+            "23:59:60.5",
+        ] {
+            assert_eq!(validate_date_time(text), Ok(()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            validate_date_time("2021-02-30"),
+            Err("day is out of range for the month")
+        );
+        assert_eq!(
+            validate_date_time("1900-02-29"),
+            Err("day is out of range for the month")
+        );
+        assert_eq!(
+            validate_date_time("2021-13-01"),
+            Err("month must be between 01 and 12")
+        );
+        assert_eq!(
+            validate_date_time("1979-05-27T25:00:00"),
+            Err("hour must be between 00 and 23")
+        );
+        assert_eq!(validate_date_time("07:32"), Err("seconds are required"));
+        assert_eq!(
+            validate_date_time("1979-05-27T07:32:00+24:00"),
+            Err("offset hour must be between 00 and 23")
+        );
+    }
+}
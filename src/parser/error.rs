@@ -2,25 +2,138 @@ use rowan::{Checkpoint, TextRange, TextSize};
 use thiserror::Error;
 
 use crate::lexer::SyntaxKind::{self, *};
-use crate::parser::Parser;
+use crate::parser::{Parser, KEY_START, VALUE_START};
 
-use super::ParseResult;
-
-#[derive(Debug, Error, Clone)]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
 pub enum ParseError {
     #[error("Unexpected end of file")]
-    UnexpectedEof,
+    UnexpectedEof { range: TextRange },
 
-    #[error("Expected token {expected:?}, got {got:?}")]
+    #[error("Expected {}, found {}", describe(.expected), .got.description())]
     Expected {
         expected: Box<[SyntaxKind]>,
         got: SyntaxKind,
-        range: Option<TextRange>,
+        range: TextRange,
+    },
+
+    #[error("Unexpected end of file, expected {}", describe(.expected))]
+    UnexpectedEofWanted {
+        expected: Box<[SyntaxKind]>,
+        range: TextRange,
+    },
+
+    #[error("Expected a value")]
+    ExpectedValue { range: TextRange },
+
+    #[error("Newlines are not allowed inside of inline tables")]
+    NewlineInInlineTable { range: TextRange },
+
+    #[error("Trailing commas are not allowed in inline tables")]
+    TrailingCommaInInlineTable { range: TextRange },
+
+    #[error("Bare keys may only contain ASCII letters, ASCII digits, `_` and `-`")]
+    InvalidBareKey { range: TextRange },
+
+    #[error("Invalid escape sequence `{escape}`")]
+    InvalidEscape { escape: String, range: TextRange },
+
+    #[error("`{escape}` is not a valid unicode scalar value")]
+    InvalidUnicodeEscape { escape: String, range: TextRange },
+
+    #[error("Invalid number: {reason}")]
+    InvalidNumber {
+        reason: &'static str,
+        range: TextRange,
+    },
+
+    #[error("Invalid date-time: {reason}")]
+    InvalidDateTime {
+        reason: &'static str,
+        range: TextRange,
     },
+}
+
+impl ParseError {
+    /// Where the error occurred. Errors at the end of the file have an empty range there.
+    pub fn range(&self) -> TextRange {
+        match self {
+            ParseError::UnexpectedEof { range }
+            | ParseError::Expected { range, .. }
+            | ParseError::UnexpectedEofWanted { range, .. }
+            | ParseError::ExpectedValue { range }
+            | ParseError::NewlineInInlineTable { range }
+            | ParseError::TrailingCommaInInlineTable { range }
+            | ParseError::InvalidBareKey { range }
+            | ParseError::InvalidEscape { range, .. }
+            | ParseError::InvalidUnicodeEscape { range, .. }
+            | ParseError::InvalidNumber { range, .. }
+            | ParseError::InvalidDateTime { range, .. } => *range,
+        }
+    }
 
-    #[error("Unexpected end of file, wanted: {0:?}")]
-    UnexpectedEofWanted(Box<[SyntaxKind]>),
-    // Stra
+    pub(super) fn range_mut(&mut self) -> &mut TextRange {
+        match self {
+            ParseError::UnexpectedEof { range }
+            | ParseError::Expected { range, .. }
+            | ParseError::UnexpectedEofWanted { range, .. }
+            | ParseError::ExpectedValue { range }
+            | ParseError::NewlineInInlineTable { range }
+            | ParseError::TrailingCommaInInlineTable { range }
+            | ParseError::InvalidBareKey { range }
+            | ParseError::InvalidEscape { range, .. }
+            | ParseError::InvalidUnicodeEscape { range, .. }
+            | ParseError::InvalidNumber { range, .. }
+            | ParseError::InvalidDateTime { range, .. } => range,
+        }
+    }
+
+    pub(super) fn is_eof(&self) -> bool {
+        matches!(
+            self,
+            ParseError::UnexpectedEof { .. } | ParseError::UnexpectedEofWanted { .. }
+        )
+    }
+
+    /// A stable identifier for the kind of error, reported as the diagnostic code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedEof { .. } | ParseError::UnexpectedEofWanted { .. } => {
+                "unexpected-eof"
+            }
+            ParseError::Expected { .. } => "unexpected-token",
+            ParseError::ExpectedValue { .. } => "expected-value",
+            ParseError::NewlineInInlineTable { .. } => "newline-in-inline-table",
+            ParseError::TrailingCommaInInlineTable { .. } => "trailing-comma-in-inline-table",
+            ParseError::InvalidBareKey { .. } => "invalid-bare-key",
+            ParseError::InvalidEscape { .. } => "invalid-escape",
+            ParseError::InvalidUnicodeEscape { .. } => "invalid-unicode-escape",
+            ParseError::InvalidNumber { .. } => "invalid-number",
+            ParseError::InvalidDateTime { .. } => "invalid-date-time",
+        }
+    }
+}
+
+/// Lists the expected tokens as "a, b or c", with the usual sets summarized.
+fn describe(expected: &[SyntaxKind]) -> String {
+    if expected == KEY_START {
+        return Key.description().to_string();
+    }
+    if expected == VALUE_START {
+        return "a value".to_string();
+    }
+
+    let mut names: Vec<&str> = Vec::with_capacity(expected.len());
+    for kind in expected {
+        let name = kind.description();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    match names.split_last() {
+        None => "nothing".to_string(),
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+    }
 }
 
 impl<'a> Parser<'a> {
@@ -45,14 +158,4 @@ impl<'a> Parser<'a> {
         self.finish_node();
         self.get_text_position()
     }
-
-    pub fn add_error_until(&mut self, mut e: ParseError, predicate: impl Fn(SyntaxKind) -> bool) {
-        if let ParseError::Expected { ref mut range, .. } = e {
-            *range = Some(self.error_node_until(predicate));
-            self.errors.push(e);
-        } else {
-            self.errors.push(e);
-            self.finish_node();
-        }
-    }
 }
@@ -1,21 +1,58 @@
+pub mod ast;
+mod date_time;
 mod error;
 mod next;
+mod number;
+mod reparse;
 mod syntax;
+mod unescape;
 mod utils;
+mod validation;
 
 use std::{collections::VecDeque, convert::TryFrom, fmt};
 
-use rowan::{Checkpoint, GreenNode, GreenNodeBuilder, Language, SyntaxNode, TextRange, TextSize};
+use rowan::{Checkpoint, GreenNode, GreenNodeBuilder, Language, TextRange, TextSize};
 
 use crate::lexer::{
     Lexer,
     SyntaxKind::{self, *},
 };
 pub use error::ParseError;
-
-use syntax::Toml;
-
-type ParseResult<T, E = ParseError> = Result<T, E>;
+pub use number::{float_value, integer_value};
+pub use reparse::{reparse, TextEdit};
+pub use syntax::{SyntaxElement, SyntaxNode, SyntaxToken};
+pub use unescape::string_value;
+
+/// The tokens a value can start with.
+const VALUE_START: &[SyntaxKind] = &[
+    Integer,
+    Float,
+    True,
+    False,
+    OffsetDateTime,
+    LocalDateTime,
+    LocalDate,
+    LocalTime,
+    BasicString,
+    LiteralString,
+    MultiLineBasicString,
+    MultiLineLiteralString,
+    LBrace,
+    LBracket,
+];
+
+/// The tokens a key can start with. Numbers, booleans and dates made only of bare key
+/// characters are valid bare keys too.
+const KEY_START: &[SyntaxKind] = &[
+    Ident,
+    BasicString,
+    LiteralString,
+    Integer,
+    Float,
+    True,
+    False,
+    LocalDate,
+];
 
 macro_rules! expect_match {
     ($p:expr, $( $token:ident => $do:expr ),+ $(,)?) => {
@@ -68,6 +105,7 @@ pub struct Parser<'a> {
     builder: GreenNodeBuilder<'static>,
     buffer: VecDeque<(SyntaxKind, &'a str)>,
     index: TextSize,
+    len: TextSize,
     errors: Vec<ParseError>,
 }
 
@@ -87,6 +125,20 @@ impl<'a> Parser<'a> {
             buffer,
             errors,
             index: TextSize::from(0),
+            len: TextSize::of(input),
+        }
+    }
+
+    /// Parses tokens that were lexed before, like the ones the reparser puts together from
+    /// the old tree and the text around an edit.
+    fn from_tokens(tokens: Vec<(SyntaxKind, &'a str)>) -> Parser<'a> {
+        Parser {
+            lexer: Lexer::new(""),
+            builder: GreenNodeBuilder::new(),
+            len: tokens.iter().map(|(_, s)| TextSize::of(*s)).sum(),
+            buffer: tokens.into(),
+            errors: Vec::new(),
+            index: TextSize::from(0),
         }
     }
 
@@ -104,29 +156,92 @@ impl<'a> Parser<'a> {
                 break;
             }
 
-            self.parse_contents();
+            self.parse_entry();
         }
         self.finish_node();
 
-        AST {
-            node: self.builder.finish(),
-            errors: self.errors,
+        self.into_ast()
+    }
+
+    /// Parses the whole input as a single array, as the reparser does for edits inside of
+    /// one. Returns `None` if anything follows the array.
+    fn parse_array_fragment(mut self) -> Option<AST> {
+        self.parse_array();
+        if self.peek_raw().is_some() {
+            return None;
         }
+        Some(self.into_ast())
     }
 
-    fn parse_contents(&mut self) {
-        expect_match!(self,
-            Ident => self.parse_assign(),
-        );
+    fn into_ast(self) -> AST {
+        let node = self.builder.finish();
+        let mut errors = self.errors;
+        errors.extend(validation::validate(&SyntaxNode::new_root(node.clone())));
+        // Validation errors come last, so sort them in between the others.
+        errors.sort_by_key(|e| e.range().start());
+
+        AST { node, errors }
+    }
+
+    fn parse_entry(&mut self) {
+        let recover = |k| k == Newline;
+        if self.expect_or_skip(KEY_START, recover).is_some() {
+            self.parse_assign(recover);
+            self.expect_line_end();
+        }
     }
 
-    fn parse_assign(&mut self) {
+    /// Parses a key/value pair, skipping to a token matching `recover` after an error.
+    fn parse_assign(&mut self, recover: impl Fn(SyntaxKind) -> bool + Copy) {
         self.start_node(Assign);
-        self.expect_sequential(&[Ident, Equal]);
-        self.parse_rhs();
+        self.parse_key();
+        if self.expect_or_skip(&[Equal], recover).is_some() {
+            self.bump();
+            self.parse_value(recover);
+        }
+        self.finish_node();
+    }
+
+    /// Parses a possibly dotted key. The caller has to make sure the next token starts a key.
+    fn parse_key(&mut self) {
+        self.start_node(Key);
+        loop {
+            self.parse_key_segment();
+
+            // Whitespace after the key belongs to the parent, so look past it first.
+            if self.peek_non_trivia() != Some(Dot) {
+                break;
+            }
+            self.bump();
+            if self.expect_or_skip(KEY_START, |_| true).is_none() {
+                break;
+            }
+        }
         self.finish_node();
     }
 
+    fn parse_key_segment(&mut self) {
+        match self.peek() {
+            Some((Float, text)) if text.contains('.') => {
+                // Something like `1.5 = true` is the dotted key `1`.`5`, but lexes as a float.
+                self.next();
+                for (i, part) in text.split('.').enumerate() {
+                    if i > 0 {
+                        self.token(Dot, ".");
+                    }
+                    self.start_node(KeySegment);
+                    self.token(Ident, part);
+                    self.finish_node();
+                }
+            }
+            _ => {
+                self.start_node(KeySegment);
+                self.bump();
+                self.finish_node();
+            }
+        }
+    }
+
     fn parse_all_headers(&mut self) {
         loop {
             self.accept_all(Newline);
@@ -154,124 +269,167 @@ impl<'a> Parser<'a> {
         expect_match!(self,
             LBracket => {
                 self.bump();
-                if self.accept(LBracket) {
-                    self.start_node_at(checkpoint, ArrayHeader);
-                    self.parse_table_ident();
-                    self.expect_sequential(&[RBracket, RBracket, Newline]);
-                    self.parse_table_contents();
-                    self.finish_node();
-                } else {
-                    self.start_node_at(checkpoint, TableHeader);
-                    self.parse_table_ident();
-                    self.expect_sequential(&[RBracket, Newline]);
-                    self.parse_table_contents();
-                    self.finish_node();
-                }
+                let array = self.accept(LBracket);
+                self.start_node_at(checkpoint, if array { ArrayHeader } else { TableHeader });
+                self.parse_header_key(array);
+                self.parse_table_contents();
+                self.finish_node();
             },
         );
     }
 
-    fn parse_table_ident(&mut self) {
-        self.expect_bump(Ident);
-        if self.accept(Dot) {
-            self.parse_table_ident()
+    fn parse_header_key(&mut self, array: bool) {
+        if self
+            .expect_or_skip(KEY_START, |k| k == RBracket || k == Newline)
+            .is_some()
+        {
+            self.parse_key();
         }
+
+        let brackets = if array { 2 } else { 1 };
+        for _ in 0..brackets {
+            if self.expect_or_skip(&[RBracket], |k| k == Newline).is_none() {
+                break;
+            }
+            self.bump();
+        }
+        self.expect_line_end();
     }
 
     fn parse_table_contents(&mut self) {
         loop {
             self.accept_all(Newline);
 
-            if self.peek_token().map(|k| k == LBracket).unwrap_or(true) {
-                return;
-            }
-
-            if self.peek().is_none() {
-                return;
+            match self.peek_token() {
+                None | Some(LBracket) => return,
+                Some(_) => self.parse_entry(),
             }
-
-            expect_match!(self,
-                Ident => self.parse_assign(),
-                RBracket => {
-                    break;
-                },
-            );
         }
     }
 
-    fn parse_rhs(&mut self) -> Option<SyntaxKind> {
-        expect_match!(self,
-            Number => self.bump(),
-            String => self.bump(),
-            LBrace => self.parse_table(),
-            LBracket => self.parse_array(),
-        )
-    }
-
-    fn parse_rhs_no_advance(&mut self) -> ParseResult<SyntaxKind> {
-        let expected = [Number, String, LBrace, LBracket];
-        let peek_tok = self
-            .peek_token()
-            .ok_or_else(|| ParseError::UnexpectedEofWanted(expected.to_vec().into_boxed_slice()))?;
-        match peek_tok {
-            Number => self.bump(),
-            String => self.bump(),
-            LBrace => self.parse_table(),
-            LBracket => self.parse_array(),
-            got => {
-                return Err(ParseError::Expected {
-                    expected: expected.to_vec().into_boxed_slice(),
-                    got,
-                    range: None,
-                })
+    /// Parses any kind of value. If the next token cannot start one, an error is reported and
+    /// everything up to the next token matching `recover` is skipped.
+    fn parse_value(&mut self, recover: impl Fn(SyntaxKind) -> bool) {
+        match self.peek() {
+            Some((LBrace, _)) => self.parse_inline_table(),
+            Some((LBracket, _)) => self.parse_array(),
+            Some((kind, _)) if VALUE_START.contains(&kind) => self.bump(),
+            Some((got, text)) if recover(got) => {
+                let range = TextRange::at(self.get_text_position(), TextSize::of(text));
+                self.errors.push(ParseError::ExpectedValue { range });
+            }
+            Some(_) => {
+                let range = self.error_node_until(recover);
+                self.errors.push(ParseError::ExpectedValue { range });
+            }
+            None => {
+                let range = TextRange::empty(self.get_text_position());
+                self.errors.push(ParseError::ExpectedValue { range });
             }
         }
-        Ok(peek_tok)
     }
 
     fn parse_array(&mut self) {
         self.start_node(Array);
         self.expect_bump(LBracket);
 
+        let recover = |k| k == Comma || k == RBracket || k == Newline;
         loop {
-            self.accept_all_if(|k| k.is_trivia() || k == Newline);
-
-            if self.peek().is_none() {
+            self.accept_all(Newline);
+            if let None | Some(RBracket) = self.peek_token() {
                 break;
             }
 
-            if let Some(RBracket) = self.peek_token() {
-                break;
+            self.parse_value(recover);
+
+            self.accept_all(Newline);
+            match self.peek_token() {
+                Some(Comma) => self.bump(),
+                None | Some(RBracket) => break,
+                Some(kind) if VALUE_START.contains(&kind) => {
+                    // Probably just a forgotten comma, so keep parsing values.
+                    self.expect_or_skip(&[Comma, RBracket], |_| true);
+                }
+                Some(_) => {
+                    self.expect_or_skip(&[Comma, RBracket], recover);
+                    self.accept(Comma);
+                }
             }
+        }
 
-            if let Err(e) = self.parse_rhs_no_advance() {
-                if let ParseError::Expected { .. } = e {
-                    self.add_error_until(e, |k| k == RBracket);
+        self.expect_bump(RBracket);
+        self.finish_node();
+    }
+
+    fn parse_inline_table(&mut self) {
+        self.start_node(InlineTable);
+        self.expect_bump(LBrace);
+
+        let recover = |k| k == Comma || k == RBrace || k == Newline;
+        if self.inline_table_newlines() && self.peek_token() != Some(RBrace) {
+            loop {
+                // A comma without a key in front of it is only reported once it is clear
+                // whether it is a trailing comma.
+                let key_missing = self.peek_token() == Some(Comma);
+                if !key_missing && self.expect_or_skip(KEY_START, recover).is_some() {
+                    self.parse_assign(recover);
+                }
+                // The missing closing brace is reported once, below.
+                if !self.inline_table_newlines() || self.peek_token().is_none() {
                     break;
-                } else {
-                    self.errors.push(e);
-                    self.finish_node();
-                    return;
                 }
-            }
 
-            if let Some(RBracket) = self
-                .peek_until(|k| !k.is_trivia() && k != Newline)
-                .map(|(tok, _s)| tok)
-            {
-                self.accept(Comma);
-            } else {
-                self.expect_bump(Comma);
+                let mut comma = None;
+                let sep = expect_match!(self,
+                    Comma => {
+                        comma = Some(TextRange::at(self.get_text_position(), TextSize::of(',')));
+                        self.bump();
+                    },
+                    RBrace => {},
+                );
+                if sep != Some(Comma) || !self.inline_table_newlines() {
+                    break;
+                }
+                if let (Some(range), Some(RBrace)) = (comma, self.peek_token()) {
+                    self.errors
+                        .push(ParseError::TrailingCommaInInlineTable { range });
+                    break;
+                }
+                if let (true, Some(range)) = (key_missing, comma) {
+                    self.errors.push(ParseError::Expected {
+                        expected: KEY_START.to_vec().into_boxed_slice(),
+                        got: Comma,
+                        range,
+                    });
+                }
             }
-
-            self.accept_all_if(|k| k.is_trivia() || k == Newline);
         }
 
-        self.expect_bump(RBracket);
+        if self.peek_token() == Some(LBracket) {
+            // The entries only stop in front of a `[` when it starts a line, which makes it a
+            // header, so the closing brace is probably missing.
+            let position = self.get_text_position();
+            self.errors.push(ParseError::Expected {
+                expected: Box::new([RBrace]),
+                got: LBracket,
+                range: TextRange::empty(position),
+            });
+        } else {
+            self.expect_bump(RBrace);
+        }
         self.finish_node();
     }
 
-    fn parse_table(&mut self) {}
+    /// Inline tables have to be on a single line, but newlines inside of them are only
+    /// reported and skipped, unless a header follows and the closing brace was forgotten.
+    /// Returns whether the inline table continues.
+    fn inline_table_newlines(&mut self) -> bool {
+        if self.peek_token() == Some(Newline) {
+            let range = self.error_node_until(|k| k != Newline);
+            self.errors.push(ParseError::NewlineInInlineTable { range });
+        }
+        self.peek_token() != Some(LBracket)
+    }
 
     fn start_node(&mut self, kind: SyntaxKind) {
         self.builder.start_node(kind.into())
@@ -293,7 +451,9 @@ impl<'a> Parser<'a> {
                 self.token(tok, s);
             }
             None => {
-                self.errors.push(ParseError::UnexpectedEof);
+                self.errors.push(ParseError::UnexpectedEof {
+                    range: self.eof_range(),
+                });
             }
         }
     }
@@ -304,7 +464,9 @@ impl<'a> Parser<'a> {
                 self.token(tok, s);
             }
             None => {
-                self.errors.push(ParseError::UnexpectedEof);
+                self.errors.push(ParseError::UnexpectedEof {
+                    range: self.eof_range(),
+                });
             }
         }
     }
@@ -318,7 +480,9 @@ impl<'a> Parser<'a> {
         let next = self.next();
         match next {
             Some((tok, s)) => self.token(Error, s),
-            None => self.errors.push(ParseError::UnexpectedEof),
+            None => self.errors.push(ParseError::UnexpectedEof {
+                range: self.eof_range(),
+            }),
         }
     }
 }
@@ -330,10 +494,14 @@ pub struct AST {
 }
 
 impl AST {
-    pub(crate) fn node(&self) -> SyntaxNode<Toml> {
+    pub(crate) fn node(&self) -> SyntaxNode {
         SyntaxNode::new_root(self.node.clone())
     }
 
+    pub fn root(&self) -> ast::Root {
+        ast::AstNode::cast(self.node()).expect("BUG: the parser always starts with a root")
+    }
+
     pub fn errors(&self) -> Vec<ParseError> {
         self.errors.clone()
     }
@@ -376,7 +544,8 @@ mod tests {
 
                 let mut actual = std::string::String::new();
                 for error in ast.errors() {
-                    writeln!(actual, "error: {}", error).unwrap();
+                    writeln!(actual, "error[{}] @{:?}: {}", error.code(), error.range(), error)
+                        .unwrap();
                 }
                 writeln!(actual, "{}", ast.debug()).unwrap();
 
@@ -390,5 +559,12 @@ mod tests {
         use super::test_dir;
         #[test] fn let_test() { test_dir("parser/let") }
         #[test] fn array() { test_dir("parser/array") }
+        #[test] fn string() { test_dir("parser/string") }
+        #[test] fn number() { test_dir("parser/number") }
+        #[test] fn date_time() { test_dir("parser/date_time") }
+        #[test] fn inline_table() { test_dir("parser/inline_table") }
+        #[test] fn key() { test_dir("parser/key") }
+        #[test] fn value() { test_dir("parser/value") }
+        #[test] fn eof() { test_dir("parser/eof") }
     }
 }
@@ -52,20 +52,20 @@ impl<'a> Parser<'a> {
         self.peek_back_raw().map(|(tok, _s)| tok)
     }
 
-    pub(super) fn peek_n(&mut self, mut n: usize) -> Option<(SyntaxKind, &'a str)> {
+    /// Returns the next non-trivia token without eating the trivia in front of it, so that
+    /// the trivia can still end up outside of the current node.
+    pub(super) fn peek_non_trivia(&mut self) -> Option<SyntaxKind> {
+        let mut n = 0;
         loop {
-            dbg!(n);
-            dbg!(&self.buffer);
-            if let Some((tok, s)) = dbg!(self.buffer.get(n)) {
-                let tok = *tok;
-                if tok.is_trivia() {
-                    self.buffer.push_back(dbg!(self.lexer.next()?));
-                    n += 1;
-                } else {
-                    println!("return");
-                    return Some((tok, s))
-                }
+            if n == self.buffer.len() {
+                let next = self.lexer.next()?;
+                self.buffer.push_back(next);
             }
+            let (tok, _s) = self.buffer[n];
+            if !tok.is_trivia() {
+                return Some(tok);
+            }
+            n += 1;
         }
     }
 
@@ -85,9 +85,10 @@ impl<'a> Parser<'a> {
     pub(super) fn peek_is_any(&mut self, is: &[SyntaxKind]) -> Option<SyntaxKind> {
         self.peek_token()
             .or_else(|| {
-                self.errors.push(ParseError::UnexpectedEofWanted(
-                    is.to_vec().into_boxed_slice(),
-                ));
+                self.errors.push(ParseError::UnexpectedEofWanted {
+                    expected: is.to_vec().into_boxed_slice(),
+                    range: self.eof_range(),
+                });
                 None
             })
             .and_then(|tok| if is.contains(&tok) { Some(tok) } else { None })
@@ -0,0 +1,139 @@
+use std::str::FromStr;
+
+/// Returns the value of an integer token, or the reason it is not a valid TOML integer.
+pub fn integer_value(text: &str) -> Result<i64, &'static str> {
+    let (sign, unsigned) = split_sign(text);
+    let (radix, digits) = match unsigned.get(..2) {
+        Some("0x") => (16, &unsigned[2..]),
+        Some("0o") => (8, &unsigned[2..]),
+        Some("0b") => (2, &unsigned[2..]),
+        _ => (10, unsigned),
+    };
+
+    if radix != 10 && !sign.is_empty() {
+        return Err("hexadecimal, octal and binary integers cannot have a sign");
+    }
+    check_digits(digits, radix == 10)?;
+
+    let digits: String = digits.chars().filter(|&c| c != '_').collect();
+    if !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(match radix {
+            16 => "invalid digit for a hexadecimal integer",
+            8 => "invalid digit for an octal integer",
+            2 => "invalid digit for a binary integer",
+            _ => "invalid digit",
+        });
+    }
+
+    i64::from_str_radix(&format!("{}{}", sign, digits), radix)
+        .map_err(|_| "integer does not fit in 64 bits")
+}
+
+/// Returns the value of a float token, or the reason it is not a valid TOML float.
+pub fn float_value(text: &str) -> Result<f64, &'static str> {
+    let (sign, unsigned) = split_sign(text);
+    match unsigned {
+        "inf" if sign == "-" => return Ok(f64::NEG_INFINITY),
+        "inf" => return Ok(f64::INFINITY),
+        "nan" => return Ok(f64::NAN),
+        _ => (),
+    }
+
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+        None => (unsigned, None),
+    };
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], Some(&mantissa[i + 1..])),
+        None => (mantissa, None),
+    };
+
+    check_digits(integer, true)?;
+    if let Some(fraction) = fraction {
+        check_digits(fraction, false)?;
+    }
+    if let Some(exponent) = exponent {
+        let (_, exponent) = split_sign(exponent);
+        check_digits(exponent, false).map_err(|e| match e {
+            "expected digits" => "expected digits in the exponent",
+            e => e,
+        })?;
+    }
+
+    let cleaned: String = text.chars().filter(|&c| c != '_').collect();
+    f64::from_str(&cleaned).map_err(|_| "invalid float")
+}
+
+fn split_sign(text: &str) -> (&str, &str) {
+    if text.starts_with('+') || text.starts_with('-') {
+        text.split_at(1)
+    } else {
+        ("", text)
+    }
+}
+
+fn check_digits(digits: &str, no_leading_zero: bool) -> Result<(), &'static str> {
+    if digits.is_empty() {
+        return Err("expected digits");
+    }
+    if !digits.split('_').all(|part| !part.is_empty()) {
+        return Err("underscores must be surrounded by digits");
+    }
+    if no_leading_zero && digits.len() > 1 && digits.starts_with('0') {
+        return Err("leading zeros are not allowed");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers() {
+        assert_eq!(integer_value("+99"), Ok(99));
+        assert_eq!(integer_value("-17"), Ok(-17));
+        assert_eq!(integer_value("0"), Ok(0));
+        assert_eq!(integer_value("1_000_000"), Ok(1_000_000));
+        assert_eq!(integer_value("0xDEAD_beef"), Ok(0xdead_beef));
+        assert_eq!(integer_value("0o755"), Ok(0o755));
+        assert_eq!(integer_value("0b1101"), Ok(0b1101));
+        assert_eq!(integer_value("-9223372036854775808"), Ok(i64::MIN));
+    }
+
+    #[test]
+    fn invalid_integers() {
+        assert_eq!(integer_value("0123"), Err("leading zeros are not allowed"));
+        assert_eq!(integer_value("1__2"), Err("underscores must be surrounded by digits"));
+        assert_eq!(integer_value("1_"), Err("underscores must be surrounded by digits"));
+        assert_eq!(integer_value("0x_1"), Err("underscores must be surrounded by digits"));
+        assert_eq!(integer_value("0o8"), Err("invalid digit for an octal integer"));
+        assert_eq!(integer_value("0x"), Err("expected digits"));
+        assert_eq!(
+            integer_value("+0x1"),
+            Err("hexadecimal, octal and binary integers cannot have a sign")
+        );
+        assert_eq!(integer_value("9223372036854775808"), Err("integer does not fit in 64 bits"));
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(float_value("+1.0"), Ok(1.0));
+        assert_eq!(float_value("3.25"), Ok(3.25));
+        assert_eq!(float_value("-0.01"), Ok(-0.01));
+        assert_eq!(float_value("5e+22"), Ok(5e22));
+        assert_eq!(float_value("6.626e-34"), Ok(6.626e-34));
+        assert_eq!(float_value("224_617.445_991"), Ok(224_617.445_991));
+        assert_eq!(float_value("1e06"), Ok(1e6));
+        assert_eq!(float_value("-inf"), Ok(f64::NEG_INFINITY));
+        assert!(float_value("nan").unwrap().is_nan());
+    }
+
+    #[test]
+    fn invalid_floats() {
+        assert_eq!(float_value("03.14"), Err("leading zeros are not allowed"));
+        assert_eq!(float_value("1._5"), Err("underscores must be surrounded by digits"));
+        assert_eq!(float_value("1e_5"), Err("underscores must be surrounded by digits"));
+        assert_eq!(float_value("1e"), Err("expected digits in the exponent"));
+    }
+}
@@ -0,0 +1,399 @@
+//! Reparsing after an edit, reusing the parts of the old tree that the edit did not touch.
+//!
+//! Only the innermost array, or else the top level section, containing the edit is reparsed.
+//! Its tokens are taken from the old tree, except for the damaged ones around the edit, which
+//! are lexed again until the new tokens line up with the old ones. The result is only reused
+//! if parsing it on its own cannot have turned out differently than parsing it as part of the
+//! document, which is the case when it was not cut short by the end of its text. Everything
+//! else falls back to a full parse.
+
+use std::borrow::Cow;
+
+use ropey::Rope;
+use rowan::{GreenNode, GreenToken, NodeOrToken, TextRange, TextSize};
+
+use super::{
+    parse, ParseError, Parser, SyntaxElement,
+    SyntaxKind::{self, *},
+    SyntaxNode, AST,
+};
+use crate::lexer::Lexer;
+
+/// How far past the end of a token the lexer may look to tell what the token is, which is
+/// the ` 07:32` after a date like `1979-05-27`.
+const LOOKAHEAD: u32 = 6;
+
+/// A change to a document: the text in `delete` is replaced by `insert`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub delete: TextRange,
+    pub insert: String,
+}
+
+impl TextEdit {
+    /// Where `offset`, which lies after the edit, ends up once the edit is applied.
+    fn moved(&self, offset: TextSize) -> TextSize {
+        offset + TextSize::of(&self.insert) - self.delete.len()
+    }
+}
+
+/// Returns the tree of `text`, which is the text of `ast` with `edit` applied. Only the
+/// reparsed part of the text is read, unless it falls back to a full parse.
+pub fn reparse(ast: &AST, edit: &TextEdit, text: &Rope) -> AST {
+    try_reparse(ast, edit, text).unwrap_or_else(|| parse(&Cow::from(text)))
+}
+
+/// The text in `range`, which is only copied if it is split across chunks of the rope.
+fn slice(text: &Rope, range: TextRange) -> Cow<'_, str> {
+    text.byte_slice(usize::from(range.start())..usize::from(range.end()))
+        .into()
+}
+
+fn try_reparse(ast: &AST, edit: &TextEdit, text: &Rope) -> Option<AST> {
+    reparse_array(ast, edit, text).or_else(|| reparse_section(ast, edit, text))
+}
+
+fn reparse_array(ast: &AST, edit: &TextEdit, text: &Rope) -> Option<AST> {
+    let root = ast.node();
+    let inside = |node: &SyntaxNode| {
+        let range = node.text_range();
+        range.start() < edit.delete.start()
+            && edit.delete.end() < range.end()
+            && node.first_token().map(|t| t.kind()) == Some(LBracket)
+            && node.last_token().map(|t| t.kind()) == Some(RBracket)
+    };
+    let array = match root.covering_element(edit.delete) {
+        NodeOrToken::Node(node) => node,
+        NodeOrToken::Token(token) => token.parent(),
+    }
+    .ancestors()
+    .find(|node| node.kind() == Array && inside(node))?;
+
+    let old_range = array.text_range();
+    let new_range = TextRange::new(old_range.start(), edit.moved(old_range.end()));
+    let new_text = slice(text, new_range);
+    let old_tokens = lexed(Some(NodeOrToken::Node(array.clone())));
+    let tokens = relex(&old_tokens, edit, old_range, &new_text);
+    let fragment = Parser::from_tokens(tokens).parse_array_fragment()?;
+    let closed = fragment.node().last_token().map(|t| t.kind()) == Some(RBracket);
+    if !closed || fragment.errors.iter().any(ParseError::is_eof) {
+        return None;
+    }
+
+    Some(AST {
+        errors: merge_errors(&ast.errors, old_range, edit, fragment.errors)?,
+        node: array.replace_with(fragment.node),
+    })
+}
+
+fn reparse_section(ast: &AST, edit: &TextEdit, text: &Rope) -> Option<AST> {
+    let root = ast.node();
+    let children: Vec<_> = root.children_with_tokens().collect();
+    let is_header = |kind: SyntaxKind| kind == TableHeader || kind == ArrayHeader;
+
+    // The edit has to be inside of a single header section, or inside of the lines of entries
+    // in front of the first header.
+    let header = children.iter().position(|child| {
+        is_header(child.kind()) && child.text_range().contains_range(edit.delete)
+    });
+    let (first, end) = match header {
+        Some(i) => (i, i + 1),
+        None => {
+            let first = children
+                .iter()
+                .rposition(|child| {
+                    child.kind() == Newline && child.text_range().end() <= edit.delete.start()
+                })
+                .map_or(0, |i| i + 1);
+            let end = children
+                .iter()
+                .position(|child| {
+                    child.kind() == Newline && child.text_range().start() >= edit.delete.end()
+                })
+                .map_or(children.len(), |i| i + 1);
+            (first, end.max(first))
+        }
+    };
+    let run = &children[first..end];
+    let start = run
+        .first()
+        .map_or(root.text_range().end(), |child| child.text_range().start());
+    let old_range = TextRange::new(start, run.last().map_or(start, |c| c.text_range().end()));
+    if header.is_none() {
+        // The lines have to start right after a newline, which the edit cannot extend.
+        let line_start = start == 0.into() || text.byte(usize::from(start) - 1) == b'\n';
+        if !line_start || run.iter().any(|child| is_header(child.kind())) {
+            return None;
+        }
+    }
+
+    let new_range = TextRange::new(start, edit.moved(old_range.end()));
+    let new_text = slice(text, new_range);
+    let old_tokens = lexed(run.iter().cloned());
+    let tokens = relex(&old_tokens, edit, old_range, &new_text);
+    let fragment = Parser::from_tokens(tokens).parse();
+
+    // Unless the section reaches the end of the document, it has to end with a newline like
+    // before, so that neither the lexer nor the parser continue into the next one.
+    let next = children.get(end);
+    if next.is_some() {
+        let ends_line = new_range.is_empty()
+            || new_text.ends_with('\n')
+                && fragment.node().last_token().map(|t| t.kind()) == Some(Newline);
+        if !ends_line || fragment.errors.iter().any(ParseError::is_eof) {
+            return None;
+        }
+    }
+
+    // A header section cannot grow entries in front of its header, and entries in front of
+    // the first header cannot become headers if more entries follow them.
+    let new_children: Vec<_> = fragment.node().children_with_tokens().collect();
+    let valid = if header.is_some() {
+        new_children.iter().all(|child| is_header(child.kind()))
+    } else {
+        new_children.iter().all(|child| !is_header(child.kind()))
+            || next.is_none_or(|next| is_header(next.kind()))
+    };
+    if !valid {
+        return None;
+    }
+
+    let owned = |child: NodeOrToken<&GreenNode, &GreenToken>| match child {
+        NodeOrToken::Node(node) => NodeOrToken::Node(node.clone()),
+        NodeOrToken::Token(token) => NodeOrToken::Token(token.clone()),
+    };
+    let mut children: Vec<_> = ast.node.children().map(owned).collect();
+    children.splice(first..end, fragment.node.children().map(owned));
+    Some(AST {
+        errors: merge_errors(&ast.errors, old_range, edit, fragment.errors)?,
+        node: GreenNode::new(ast.node.kind(), children),
+    })
+}
+
+/// The tokens the lexer produced for `elements`. The parser splits keys like `1.5`, which
+/// lex as a float, and turns unexpected tokens into errors, so those are lexed again.
+fn lexed(elements: impl IntoIterator<Item = SyntaxElement>) -> Vec<(SyntaxKind, TextRange)> {
+    fn collect(
+        elements: impl Iterator<Item = SyntaxElement>,
+        tokens: &mut Vec<(SyntaxKind, TextRange)>,
+    ) {
+        for element in elements {
+            match element {
+                NodeOrToken::Node(node) if node.kind() != Key => {
+                    collect(node.children_with_tokens(), tokens)
+                }
+                NodeOrToken::Token(token) if token.kind() != Error => {
+                    tokens.push((token.kind(), token.text_range()))
+                }
+                element => {
+                    let mut offset = element.text_range().start();
+                    for (kind, text) in Lexer::new(&element.to_string()) {
+                        tokens.push((kind, TextRange::at(offset, TextSize::of(text))));
+                        offset += TextSize::of(text);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut tokens = Vec::new();
+    collect(elements.into_iter(), &mut tokens);
+    tokens
+}
+
+/// The tokens of `text`, which is `old_range` after `edit`, given its old tokens `old`.
+/// Lexing starts again at the first token whose lookahead reaches into the edit, and stops at
+/// the first token after the edit that ends where an old one did. The rest is carried over.
+fn relex<'a>(
+    old: &[(SyntaxKind, TextRange)],
+    edit: &TextEdit,
+    old_range: TextRange,
+    text: &'a str,
+) -> Vec<(SyntaxKind, &'a str)> {
+    let start = old_range.start();
+    let first = old
+        .iter()
+        .position(|(_, range)| range.end() + TextSize::from(LOOKAHEAD) > edit.delete.start())
+        .unwrap_or(old.len());
+    let mut tokens: Vec<_> = old[..first]
+        .iter()
+        .map(|&(kind, range)| (kind, &text[range - start]))
+        .collect();
+
+    let mut offset = old
+        .get(first)
+        .map_or(old_range.end(), |(_, range)| range.start());
+    let inserted_end = edit.delete.start() + TextSize::of(&edit.insert);
+    for (kind, token) in Lexer::new(&text[usize::from(offset - start)..]) {
+        tokens.push((kind, token));
+        offset += TextSize::of(token);
+        if offset < inserted_end {
+            continue;
+        }
+        let old_offset = offset + edit.delete.len() - TextSize::of(&edit.insert);
+        if let Ok(i) = old.binary_search_by_key(&old_offset, |(_, range)| range.end()) {
+            tokens.extend(old[i + 1..].iter().map(|&(kind, range)| {
+                let range = TextRange::new(edit.moved(range.start()), edit.moved(range.end()));
+                (kind, &text[range - start])
+            }));
+            break;
+        }
+    }
+    tokens
+}
+
+/// Replaces the errors inside of `old_range` by `new`, whose ranges are relative to its
+/// start, and moves the errors after it. Returns `None` if an error crosses its boundaries.
+fn merge_errors(
+    old: &[ParseError],
+    old_range: TextRange,
+    edit: &TextEdit,
+    mut new: Vec<ParseError>,
+) -> Option<Vec<ParseError>> {
+    let mut errors = Vec::with_capacity(old.len() + new.len());
+    let mut after = Vec::new();
+    for error in old {
+        let range = error.range();
+        if old_range.contains_range(range) {
+            continue;
+        } else if range.end() <= old_range.start() {
+            errors.push(error.clone());
+        } else if range.start() >= old_range.end() {
+            let mut error = error.clone();
+            let range = error.range_mut();
+            *range = TextRange::new(edit.moved(range.start()), edit.moved(range.end()));
+            after.push(error);
+        } else {
+            return None;
+        }
+    }
+
+    for error in &mut new {
+        let range = error.range_mut();
+        *range += old_range.start();
+    }
+    errors.extend(new);
+    errors.extend(after);
+    Some(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, fs, path::PathBuf};
+
+    use super::*;
+
+    /// Edits applied at every offset of every file in the corpus.
+    const EDITS: &[(u32, &str)] = &[
+        (0, "x"),
+        (0, " "),
+        (0, "\n"),
+        (0, "["),
+        (0, "]"),
+        (0, "{"),
+        (0, "\""),
+        (0, "'''"),
+        (0, "#"),
+        (0, "\n[t]\n"),
+        (0, "a = [1,\n"),
+        (1, ""),
+        (3, ""),
+        (3, "\n"),
+    ];
+
+    fn corpus() -> Vec<(PathBuf, String)> {
+        let dir: PathBuf = [env!("CARGO_MANIFEST_DIR"), "test_data", "reparse"]
+            .iter()
+            .collect();
+        let mut files = Vec::new();
+        for entry in dir.read_dir().expect("Failed to read dir") {
+            let path = entry.expect("Failed to read entry").path();
+            if path.extension() == Some(OsStr::new("toml")) {
+                let text = fs::read_to_string(&path).expect("Failed to read to string");
+                files.push((path, text));
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn reparse_matches_full_parse() {
+        let mut reused = 0;
+        let mut total = 0;
+        for (path, text) in corpus() {
+            let old = parse(&text);
+            let offsets = (0..=text.len()).filter(|&i| text.is_char_boundary(i));
+            for offset in offsets {
+                for &(len, insert) in EDITS {
+                    let end = offset + len as usize;
+                    if end > text.len() || !text.is_char_boundary(end) {
+                        continue;
+                    }
+                    let edit = TextEdit {
+                        delete: TextRange::new((offset as u32).into(), (end as u32).into()),
+                        insert: insert.to_string(),
+                    };
+                    let mut new_text = text.clone();
+                    new_text.replace_range(offset..end, insert);
+                    let rope = Rope::from_str(&new_text);
+
+                    let full = parse(&new_text);
+                    total += 1;
+                    if let Some(incremental) = try_reparse(&old, &edit, &rope) {
+                        reused += 1;
+                        if (&incremental.node, &incremental.errors) != (&full.node, &full.errors) {
+                            assert_eq!(
+                                (incremental.debug(), &incremental.errors),
+                                (full.debug(), &full.errors),
+                                "{} with {:?}",
+                                path.display(),
+                                edit
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        // Make sure that the corpus actually exercises the incremental paths.
+        assert!(
+            reused * 2 > total,
+            "only {} of {} edits reparsed",
+            reused,
+            total
+        );
+    }
+
+    #[test]
+    fn reuses_sections() {
+        let text = "a = 1\n[t]\nb = [1, 2]\n[u]\nc = 3\n";
+        let old = parse(text);
+        let edit = TextEdit {
+            delete: TextRange::new(17.into(), 18.into()),
+            insert: "20".to_string(),
+        };
+        let new_text = Rope::from_str("a = 1\n[t]\nb = [1, 20]\n[u]\nc = 3\n");
+        assert!(reparse_array(&old, &edit, &new_text).is_some());
+
+        let edit = TextEdit {
+            delete: TextRange::new(10.into(), 11.into()),
+            insert: "d".to_string(),
+        };
+        let new_text = Rope::from_str("a = 1\n[t]\nd = [1, 2]\n[u]\nc = 3\n");
+        let new = reparse_section(&old, &edit, &new_text).unwrap();
+        // The untouched section is carried over from the old tree.
+        let old_u = old
+            .node
+            .children()
+            .nth(3)
+            .and_then(|c| c.into_node())
+            .unwrap();
+        let new_u = new
+            .node
+            .children()
+            .nth(3)
+            .and_then(|c| c.into_node())
+            .unwrap();
+        assert_eq!(old_u.kind(), TableHeader.into());
+        assert_eq!(old_u, new_u);
+    }
+}
@@ -2,7 +2,11 @@ use crate::lexer::SyntaxKind;
 use num_traits::{FromPrimitive, ToPrimitive};
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-pub(crate) enum Toml {}
+pub enum Toml {}
+
+pub type SyntaxNode = rowan::SyntaxNode<Toml>;
+pub type SyntaxToken = rowan::SyntaxToken<Toml>;
+pub type SyntaxElement = rowan::SyntaxElement<Toml>;
 
 impl rowan::Language for Toml {
     type Kind = SyntaxKind;
@@ -0,0 +1,194 @@
+use std::char;
+
+use rowan::{TextRange, TextSize};
+
+use super::{ParseError, SyntaxKind};
+
+/// Returns the value of a string token, with its delimiters stripped and its escapes
+/// resolved. `on_error` is called for every invalid escape, with a range starting at `offset`.
+pub fn string_value(
+    kind: SyntaxKind,
+    text: &str,
+    offset: TextSize,
+    mut on_error: impl FnMut(ParseError),
+) -> String {
+    let (delimiter, multi_line, basic) = match kind {
+        SyntaxKind::BasicString => (1, false, true),
+        SyntaxKind::LiteralString => (1, false, false),
+        SyntaxKind::MultiLineBasicString => (3, true, true),
+        SyntaxKind::MultiLineLiteralString => (3, true, false),
+        _ => panic!("BUG: {:?} is not a string", kind),
+    };
+
+    let mut start = delimiter;
+    let end = text.len() - delimiter;
+    if multi_line {
+        // A newline immediately following the opening delimiter is trimmed.
+        if text[start..].starts_with('\n') {
+            start += 1;
+        } else if text[start..].starts_with("\r\n") {
+            start += 2;
+        }
+    }
+    let content = &text[start..end];
+
+    if !basic {
+        return content.to_string();
+    }
+
+    let range = |from: usize, to: usize| {
+        TextRange::new(
+            offset + TextSize::from((start + from) as u32),
+            offset + TextSize::from((start + to) as u32),
+        )
+    };
+
+    let mut value = String::with_capacity(content.len());
+    let mut chars = content.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+
+        let (j, escaped) = match chars.next() {
+            Some(next) => next,
+            None => {
+                on_error(ParseError::InvalidEscape {
+                    escape: "\\".to_string(),
+                    range: range(i, i + 1),
+                });
+                break;
+            }
+        };
+        match escaped {
+            'b' => value.push('\u{8}'),
+            't' => value.push('\t'),
+            'n' => value.push('\n'),
+            'f' => value.push('\u{c}'),
+            'r' => value.push('\r'),
+            '"' => value.push('"'),
+            '\\' => value.push('\\'),
+            'u' | 'U' => {
+                let len = if escaped == 'u' { 4 } else { 8 };
+                let mut end = j + 1;
+                while end - (j + 1) < len
+                    && content[end..].starts_with(|c: char| c.is_ascii_hexdigit())
+                {
+                    chars.next();
+                    end += 1;
+                }
+
+                let escape = &content[i..end];
+                if end - (j + 1) < len {
+                    on_error(ParseError::InvalidEscape {
+                        escape: escape.to_string(),
+                        range: range(i, end),
+                    });
+                    continue;
+                }
+
+                match u32::from_str_radix(&content[j + 1..end], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                {
+                    Some(c) => value.push(c),
+                    None => on_error(ParseError::InvalidUnicodeEscape {
+                        escape: escape.to_string(),
+                        range: range(i, end),
+                    }),
+                }
+            }
+            ' ' | '\t' | '\r' | '\n' if multi_line => {
+                // A line ending backslash trims all whitespace and newlines up to the next
+                // non-whitespace character, but only if nothing but whitespace follows it
+                // on its line.
+                let rest = &content[j..];
+                let blank = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+                if rest[blank..].starts_with('\n') || rest[blank..].starts_with("\r\n") {
+                    while chars
+                        .peek()
+                        .is_some_and(|&(_, c)| matches!(c, ' ' | '\t' | '\r' | '\n'))
+                    {
+                        chars.next();
+                    }
+                } else {
+                    let end = j + blank.max(escaped.len_utf8());
+                    on_error(ParseError::InvalidEscape {
+                        escape: content[i..end].to_string(),
+                        range: range(i, end),
+                    });
+                }
+            }
+            c => on_error(ParseError::InvalidEscape {
+                escape: content[i..j + c.len_utf8()].to_string(),
+                range: range(i, j + c.len_utf8()),
+            }),
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SyntaxKind::*;
+
+    fn check(kind: SyntaxKind, text: &str, expected: &str, expected_errors: &[(&str, u32, u32)]) {
+        let mut errors = Vec::new();
+        let value = string_value(kind, text, TextSize::from(0), |e| errors.push(e));
+        assert_eq!(value, expected);
+
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|e| match e {
+                ParseError::InvalidEscape { escape, range }
+                | ParseError::InvalidUnicodeEscape { escape, range } => {
+                    (escape.as_str(), range.start().into(), range.end().into())
+                }
+                _ => panic!("unexpected error {:?}", e),
+            })
+            .collect();
+        assert_eq!(errors, expected_errors);
+    }
+
+    #[test]
+    fn escapes() {
+        check(
+            BasicString,
+            r#""a\tb\"\\\u00e9\U0001F600""#,
+            "a\tb\"\\é😀",
+            &[],
+        );
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        check(BasicString, r#""a\qb""#, "ab", &[(r"\q", 2, 4)]);
+        check(BasicString, r#""\u12""#, "", &[(r"\u12", 1, 5)]);
+        check(BasicString, r#""\uD800""#, "", &[(r"\uD800", 1, 7)]);
+        check(BasicString, r#""\U00110000""#, "", &[(r"\U00110000", 1, 11)]);
+    }
+
+    #[test]
+    fn literal_strings_have_no_escapes() {
+        check(LiteralString, r"'C:\q'", r"C:\q", &[]);
+        check(MultiLineLiteralString, "'''\n\\q\n'''", "\\q\n", &[]);
+    }
+
+    #[test]
+    fn line_ending_backslash() {
+        check(
+            MultiLineBasicString,
+            "\"\"\"\nThe quick \\  \n\n   brown\"\"\"",
+            "The quick brown",
+            &[],
+        );
+        check(
+            MultiLineBasicString,
+            "\"\"\"a\\ b\"\"\"",
+            "ab",
+            &[("\\ ", 4, 6)],
+        );
+    }
+}
@@ -10,6 +10,11 @@ impl<'a> Parser<'a> {
         self.index
     }
 
+    /// The empty range at the end of the document, where end of file errors are reported.
+    pub(super) fn eof_range(&self) -> TextRange {
+        TextRange::empty(self.len)
+    }
+
     pub(super) fn bump_until<F>(&mut self, predicate: F)
     where
         F: Fn(SyntaxKind) -> bool,
@@ -39,7 +44,7 @@ impl<'a> Parser<'a> {
                 self.errors.push(ParseError::Expected {
                     expected: allowed_slice.to_vec().into_boxed_slice(),
                     got: kind,
-                    range: Some(TextRange::new(start, end)),
+                    range: TextRange::new(start, end),
                 });
 
                 self.peek_token()
@@ -47,13 +52,58 @@ impl<'a> Parser<'a> {
         };
 
         if next.is_none() {
-            self.errors.push(ParseError::UnexpectedEofWanted(
-                allowed_slice.to_vec().into_boxed_slice(),
-            ));
+            self.errors.push(ParseError::UnexpectedEofWanted {
+                expected: allowed_slice.to_vec().into_boxed_slice(),
+                range: self.eof_range(),
+            });
         }
         next
     }
 
+    /// Returns the next token if it is one of `expected`. Otherwise reports an error and
+    /// skips ahead to the next token matching `recover`, which is left for the caller.
+    pub(super) fn expect_or_skip(
+        &mut self,
+        expected: &[SyntaxKind],
+        recover: impl Fn(SyntaxKind) -> bool,
+    ) -> Option<SyntaxKind> {
+        match self.peek() {
+            Some((kind, _)) if expected.contains(&kind) => Some(kind),
+            Some((got, text)) if recover(got) => {
+                let position = self.get_text_position();
+                self.errors.push(ParseError::Expected {
+                    expected: expected.to_vec().into_boxed_slice(),
+                    got,
+                    range: TextRange::at(position, TextSize::of(text)),
+                });
+                None
+            }
+            Some((got, _)) => {
+                let range = self.error_node_until(recover);
+                self.errors.push(ParseError::Expected {
+                    expected: expected.to_vec().into_boxed_slice(),
+                    got,
+                    range,
+                });
+                None
+            }
+            None => {
+                self.errors.push(ParseError::UnexpectedEofWanted {
+                    expected: expected.to_vec().into_boxed_slice(),
+                    range: self.eof_range(),
+                });
+                None
+            }
+        }
+    }
+
+    /// Expects the end of an entry or header, reporting anything else left on its line.
+    pub(super) fn expect_line_end(&mut self) {
+        if self.peek_token().is_some() {
+            self.expect_or_skip(&[Newline], |k| k == Newline);
+        }
+    }
+
     pub(super) fn expect_bump(&mut self, expected: SyntaxKind) {
         if self.expect_peek_any(&[expected]).is_some() {
             self.bump();
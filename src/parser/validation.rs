@@ -0,0 +1,58 @@
+use super::{
+    date_time::validate_date_time,
+    number::{float_value, integer_value},
+    syntax::SyntaxNode,
+    unescape::string_value,
+    ParseError,
+    SyntaxKind::*,
+};
+
+/// Checks the contents of tokens that the lexer accepted as a whole, such as the escapes
+/// inside of strings.
+pub(super) fn validate(root: &SyntaxNode) -> Vec<ParseError> {
+    let mut errors = Vec::new();
+    for token in root.descendants_with_tokens().filter_map(|it| it.into_token()) {
+        let kind = token.kind();
+        let in_key = token.parent().kind() == KeySegment;
+        if in_key && !kind.is_string() {
+            if !token.text().chars().all(is_bare_key) {
+                errors.push(ParseError::InvalidBareKey {
+                    range: token.text_range(),
+                });
+            }
+            continue;
+        }
+
+        if kind.is_date_time() {
+            if let Err(reason) = validate_date_time(token.text()) {
+                errors.push(ParseError::InvalidDateTime {
+                    reason,
+                    range: token.text_range(),
+                });
+            }
+        }
+
+        let number = match kind {
+            Integer => integer_value(token.text()).err(),
+            Float => float_value(token.text()).err(),
+            _ if kind.is_string() => {
+                string_value(kind, token.text(), token.text_range().start(), |e| {
+                    errors.push(e)
+                });
+                None
+            }
+            _ => None,
+        };
+        if let Some(reason) = number {
+            errors.push(ParseError::InvalidNumber {
+                reason,
+                range: token.text_range(),
+            });
+        }
+    }
+    errors
+}
+
+fn is_bare_key(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
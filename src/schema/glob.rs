@@ -0,0 +1,77 @@
+//! The globs that map documents to schemas in the configuration, like `**/Cargo.toml`.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    source: String,
+}
+
+impl Glob {
+    /// `*` matches within a path segment, `**` across segments and `?` a single character.
+    /// A glob without a `/` matches file names in any directory.
+    pub fn new(source: &str) -> Glob {
+        let source = if source.contains('/') {
+            source.trim_start_matches("./").to_string()
+        } else {
+            format!("**/{}", source)
+        };
+        Glob { source }
+    }
+
+    /// Matches a path with `/` separators, relative to the workspace.
+    pub fn is_match(&self, path: &str) -> bool {
+        let glob: Vec<char> = self.source.chars().collect();
+        let path: Vec<char> = path.chars().collect();
+        matches(&glob, &path)
+    }
+}
+
+fn matches(glob: &[char], path: &[char]) -> bool {
+    match glob {
+        [] => path.is_empty(),
+        // `**/` matches no directories at all, or any number of them.
+        ['*', '*', '/', rest @ ..] => {
+            matches(rest, path)
+                || (0..path.len())
+                    .filter(|&i| path[i] == '/')
+                    .any(|i| matches(rest, &path[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != '/')
+            .any(|i| matches(rest, &path[i..])),
+        ['?', rest @ ..] => {
+            matches!(path.first(), Some(c) if *c != '/') && matches(rest, &path[1..])
+        }
+        [c, rest @ ..] => path.first() == Some(c) && matches(rest, &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching() {
+        let cases: &[(&str, &str, bool)] = &[
+            ("Cargo.toml", "Cargo.toml", true),
+            ("Cargo.toml", "crates/a/Cargo.toml", true),
+            ("./Cargo.toml", "crates/a/Cargo.toml", false),
+            ("crates/*/Cargo.toml", "crates/a/Cargo.toml", true),
+            ("crates/*/Cargo.toml", "crates/a/b/Cargo.toml", false),
+            ("crates/**/Cargo.toml", "crates/a/b/Cargo.toml", true),
+            ("crates/**/Cargo.toml", "crates/Cargo.toml", true),
+            ("config/*.toml", "config/app.toml", true),
+            ("config/?.toml", "config/ab.toml", false),
+            ("**", "any/thing.toml", true),
+        ];
+        for &(glob, path, expected) in cases {
+            assert_eq!(
+                Glob::new(glob).is_match(path),
+                expected,
+                "{} on {}",
+                glob,
+                path
+            );
+        }
+    }
+}
@@ -0,0 +1,399 @@
+//! JSON Schemas for documents, which are found through a `#:schema ./path.json` comment in
+//! the document or the globs of the configuration, and only ever loaded from local files.
+
+mod glob;
+mod validate;
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use log::warn;
+use lsp_types::Url;
+use regex::Regex;
+use rowan::TextRange;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{
+    lexer::SyntaxKind,
+    parser::AST,
+    semantic::{PathSegment, ValueKind},
+};
+
+use self::glob::Glob;
+
+#[derive(Debug, Clone)]
+pub struct Schema {
+    root: Value,
+    /// The compiled `pattern`s and `patternProperties`, by their source. Invalid ones, and
+    /// ones that need ECMA 262 features like lookarounds and backreferences, are left out and
+    /// never match. The others match in linear time, whatever the pattern.
+    regexes: HashMap<String, Regex>,
+}
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl Schema {
+    pub fn new(root: Value) -> Schema {
+        let mut regexes = HashMap::new();
+        compile_patterns(&root, &mut regexes);
+        Schema { root, regexes }
+    }
+
+    pub fn load(path: &Path) -> Result<Schema, LoadError> {
+        let text = fs::read_to_string(path)?;
+        Ok(Schema::new(serde_json::from_str(&text)?))
+    }
+
+    /// The schemas that apply to the value at `path`: the ones that `properties`,
+    /// `patternProperties`, `additionalProperties` and `items` lead to, and the ones those
+    /// refer to and have as branches of `allOf`, `anyOf` and `oneOf`.
+    pub fn resolve(&self, path: &[PathSegment]) -> Vec<&Value> {
+        let mut schemas = Vec::new();
+        self.expand(&self.root, 0, &mut schemas);
+        for segment in path {
+            let mut next = Vec::new();
+            for schema in schemas {
+                match segment {
+                    PathSegment::Key(key) => {
+                        for child in self.children(schema, key) {
+                            self.expand(child, 0, &mut next);
+                        }
+                    }
+                    PathSegment::Index(i) => {
+                        let items = match schema.get("items") {
+                            Some(Value::Array(items)) => items.get(*i),
+                            items => items,
+                        };
+                        if let Some(items) = items {
+                            self.expand(items, 0, &mut next);
+                        }
+                    }
+                }
+            }
+            schemas = next;
+        }
+        schemas
+    }
+
+    /// `schema` with the schemas it refers to and has as branches.
+    pub fn expanded<'a>(&'a self, schema: &'a Value) -> Vec<&'a Value> {
+        let mut schemas = Vec::new();
+        self.expand(schema, 0, &mut schemas);
+        schemas
+    }
+
+    fn expand<'a>(&'a self, schema: &'a Value, refs: u32, schemas: &mut Vec<&'a Value>) {
+        if refs > validate::MAX_REFS || !schema.is_object() {
+            return;
+        }
+        schemas.push(schema);
+        if let Some(target) = self.target(schema) {
+            self.expand(target, refs + 1, schemas);
+        }
+        for keyword in &["allOf", "anyOf", "oneOf"] {
+            let branches = schema.get(keyword).and_then(Value::as_array);
+            for branch in branches.into_iter().flatten() {
+                self.expand(branch, refs + 1, schemas);
+            }
+        }
+    }
+
+    /// The schemas of the value at `key` in a table that `schema` describes.
+    fn children<'a>(&'a self, schema: &'a Value, key: &str) -> Vec<&'a Value> {
+        if let Some(property) = schema.get("properties").and_then(|it| it.get(key)) {
+            return vec![property];
+        }
+        let patterns = schema.get("patternProperties").and_then(Value::as_object);
+        let matching: Vec<_> = patterns
+            .into_iter()
+            .flatten()
+            .filter(|(pattern, _)| {
+                let regex = self.regexes.get(pattern.as_str());
+                regex.is_some_and(|regex| regex.is_match(key))
+            })
+            .map(|(_, property)| property)
+            .collect();
+        if !matching.is_empty() {
+            return matching;
+        }
+        schema.get("additionalProperties").into_iter().collect()
+    }
+
+    /// Follows a `$ref` to a definition in the same file, like `#/definitions/dependency`.
+    fn target<'a>(&'a self, schema: &'a Value) -> Option<&'a Value> {
+        let reference = schema.get("$ref")?.as_str()?;
+        let pointer = reference.strip_prefix('#')?;
+        let target = self.root.pointer(pointer);
+        if target.is_none() {
+            warn!("cannot resolve the schema reference `{}`", reference);
+        }
+        target
+    }
+}
+
+/// The keys that `schemas` name in their `properties`, with the schema of each. A key that
+/// several of them name gets the schema of the first.
+pub fn properties<'a>(schemas: &[&'a Value]) -> Vec<(&'a str, &'a Value)> {
+    let mut properties: Vec<(&str, &Value)> = Vec::new();
+    for schema in schemas {
+        let named = schema.get("properties").and_then(Value::as_object);
+        for (key, property) in named.into_iter().flatten() {
+            if properties.iter().all(|(known, _)| known != key) {
+                properties.push((key, property));
+            }
+        }
+    }
+    properties
+}
+
+/// The names in the `type` of a schema.
+pub fn types(schema: &Value) -> Vec<&str> {
+    match schema.get("type") {
+        Some(Value::String(name)) => vec![name],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether the `type` of a schema allows a value of the given kind.
+pub fn allows(schema: &Value, kind: &ValueKind) -> bool {
+    let types = types(schema);
+    types.is_empty() || types.iter().any(|name| validate::has_type(kind, name))
+}
+
+/// The name of a schema type, as TOML calls it.
+pub fn type_name(name: &str) -> &'static str {
+    match name {
+        "string" => "string",
+        "integer" => "integer",
+        "number" => "number",
+        "boolean" => "boolean",
+        "array" => "array",
+        "object" => "table",
+        _ => "null",
+    }
+}
+
+/// A JSON scalar as a TOML value.
+pub fn literal(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(format!(
+            "\"{}\"",
+            text.replace('\\', "\\\\").replace('"', "\\\"")
+        )),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn compile_patterns(schema: &Value, regexes: &mut HashMap<String, Regex>) {
+    let mut compile = |source: &str| {
+        if regexes.contains_key(source) {
+            return;
+        }
+        match Regex::new(source) {
+            Ok(regex) => {
+                regexes.insert(source.to_string(), regex);
+            }
+            Err(err) => warn!("ignoring the schema pattern `{}`: {}", source, err),
+        }
+    };
+    if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+        compile(pattern);
+    }
+    if let Some(patterns) = schema.get("patternProperties").and_then(Value::as_object) {
+        patterns.keys().for_each(|pattern| compile(pattern));
+    }
+    match schema {
+        Value::Object(object) => object
+            .values()
+            .for_each(|value| compile_patterns(value, regexes)),
+        Value::Array(values) => values
+            .iter()
+            .for_each(|value| compile_patterns(value, regexes)),
+        _ => (),
+    }
+}
+
+/// The schemas of the configuration: a `schemas` object that maps globs, matched against
+/// paths relative to the workspace, to the paths of schemas.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaConfig {
+    associations: Vec<(Glob, String)>,
+}
+
+impl SchemaConfig {
+    pub fn from_settings(settings: Option<&Value>) -> SchemaConfig {
+        let schemas = settings
+            .and_then(|settings| settings.get("schemas"))
+            .and_then(Value::as_object);
+        let associations = schemas
+            .into_iter()
+            .flatten()
+            .filter_map(|(glob, path)| Some((Glob::new(glob), path.as_str()?.to_string())))
+            .collect();
+        SchemaConfig { associations }
+    }
+}
+
+/// Where the schema of a document is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub path: PathBuf,
+    /// The `#:schema` comment that names the schema, if it is not from the configuration.
+    pub directive: Option<TextRange>,
+}
+
+/// Finds the schema of the document at `path`. The `#:schema` comment wins over the
+/// configuration, and its path is relative to the document.
+pub fn locate(
+    ast: &AST,
+    path: &Path,
+    workspace: Option<&Path>,
+    config: &SchemaConfig,
+) -> Option<Source> {
+    if let Some((schema, range)) = directive(ast) {
+        let schema = match Url::parse(&schema) {
+            Ok(url) => url.to_file_path().ok()?,
+            Err(_) => path.parent().unwrap_or(path).join(schema),
+        };
+        return Some(Source {
+            path: schema,
+            directive: Some(range),
+        });
+    }
+
+    let relative = workspace
+        .and_then(|workspace| path.strip_prefix(workspace).ok())
+        .unwrap_or(path);
+    let relative: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    let relative = relative.join("/");
+    let (_, schema) = config
+        .associations
+        .iter()
+        .find(|(glob, _)| glob.is_match(&relative))?;
+    let schema = match workspace {
+        Some(workspace) => workspace.join(schema),
+        None => PathBuf::from(schema),
+    };
+    Some(Source {
+        path: schema,
+        directive: None,
+    })
+}
+
+/// The path of a `#:schema` comment, with the range of the comment.
+fn directive(ast: &AST) -> Option<(String, TextRange)> {
+    ast.node()
+        .descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .filter(|token| token.kind() == SyntaxKind::Comment)
+        .find_map(|token| {
+            let path = token.text().strip_prefix("#:schema")?;
+            if !path.starts_with(char::is_whitespace) {
+                return None;
+            }
+            Some((path.trim().to_string(), token.text_range()))
+        })
+}
+
+type Loaded = Result<Arc<Schema>, String>;
+
+/// The schemas that were loaded, which are loaded again once their file changes.
+#[derive(Debug, Default)]
+pub struct Store {
+    loaded: HashMap<PathBuf, (Option<SystemTime>, Loaded)>,
+}
+
+impl Store {
+    pub fn get(&mut self, path: &Path) -> Loaded {
+        let modified = fs::metadata(path).and_then(|it| it.modified()).ok();
+        match self.loaded.get(path) {
+            Some((loaded, schema)) if modified.is_some() && *loaded == modified => {
+                return schema.clone();
+            }
+            _ => (),
+        }
+        let schema = Schema::load(path)
+            .map(Arc::new)
+            .map_err(|err| format!("Cannot load the schema {}: {}", path.display(), err));
+        self.loaded
+            .insert(path.to_path_buf(), (modified, schema.clone()));
+        schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::parse, semantic};
+
+    #[test]
+    fn sources() {
+        let config = SchemaConfig::from_settings(Some(&serde_json::json!({
+            "schemas": { "crates/*/Cargo.toml": "schemas/cargo.json" },
+        })));
+        let workspace = Path::new("/work");
+
+        let ast = parse("#:schema ../app.json\na = 1\n");
+        assert_eq!(
+            locate(
+                &ast,
+                Path::new("/work/config/a.toml"),
+                Some(workspace),
+                &config
+            ),
+            Some(Source {
+                path: "/work/config/../app.json".into(),
+                directive: Some(TextRange::new(0.into(), 20.into())),
+            })
+        );
+
+        let ast = parse("[package]\n");
+        let path = Path::new("/work/crates/a/Cargo.toml");
+        assert_eq!(
+            locate(&ast, path, Some(workspace), &config),
+            Some(Source {
+                path: "/work/schemas/cargo.json".into(),
+                directive: None,
+            })
+        );
+        let path = Path::new("/work/Cargo.toml");
+        assert_eq!(locate(&ast, path, Some(workspace), &config), None);
+    }
+
+    #[test]
+    fn patterns() {
+        let check = |pattern: &str, value: String| {
+            let schema = Schema::new(serde_json::json!({
+                "properties": { "a": { "pattern": pattern } },
+            }));
+            let ast = parse(&format!("a = \"{}\"\n", value));
+            schema.validate(&semantic::lower(&ast.root()).root).len()
+        };
+        // Neither a long match nor a pattern that backtracks a lot takes long.
+        assert_eq!(check("^[a-z]*$", "a".repeat(20_000)), 0);
+        assert_eq!(check("^(a+)+$", format!("{}!", "a".repeat(28))), 1);
+        assert_eq!(check("^b", "ab".to_string()), 1);
+        assert_eq!(check("b", "ab".to_string()), 0);
+        // Lookarounds are not supported, so the pattern is ignored.
+        assert_eq!(check("^(?!a)", "ab".to_string()), 0);
+    }
+}
@@ -0,0 +1,465 @@
+//! Checks the value tree of a document against a schema. Dates count as strings, and tables
+//! as objects.
+
+use rowan::TextRange;
+use serde_json::{Map, Value as Json};
+use thiserror::Error;
+
+use super::{type_name, types, Schema};
+use crate::semantic::{Array, ArrayKind, Entry, Table, TableKind, ValueKind};
+
+/// How many `$ref`s in a row are followed before giving up on a schema that refers to itself.
+pub(super) const MAX_REFS: u32 = 32;
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum SchemaError {
+    #[error("Expected {}, found {}", .expected.join(" or "), .found)]
+    Type {
+        expected: Vec<&'static str>,
+        found: &'static str,
+        range: TextRange,
+    },
+
+    #[error("Missing required key `{key}`")]
+    Required { key: String, range: TextRange },
+
+    #[error("Key `{key}` is not allowed here")]
+    AdditionalProperty { key: String, range: TextRange },
+
+    #[error("No value is allowed here")]
+    Forbidden { range: TextRange },
+
+    #[error("Expected one of {}", .allowed.join(", "))]
+    Enum {
+        allowed: Vec<String>,
+        range: TextRange,
+    },
+
+    #[error("`{value}` does not match the pattern `{pattern}`")]
+    Pattern {
+        value: String,
+        pattern: String,
+        range: TextRange,
+    },
+
+    #[error("Expected a number {} {}", if *.exclusive { "greater than" } else { "of at least" }, .limit)]
+    TooSmall {
+        limit: f64,
+        exclusive: bool,
+        range: TextRange,
+    },
+
+    #[error("Expected a number {} {}", if *.exclusive { "less than" } else { "of at most" }, .limit)]
+    TooLarge {
+        limit: f64,
+        exclusive: bool,
+        range: TextRange,
+    },
+
+    #[error("Expected at least {limit} characters")]
+    TooShort { limit: u64, range: TextRange },
+
+    #[error("Expected at most {limit} characters")]
+    TooLong { limit: u64, range: TextRange },
+
+    #[error("Expected at least {limit} elements")]
+    TooFewItems { limit: u64, range: TextRange },
+
+    #[error("Expected at most {limit} elements")]
+    TooManyItems { limit: u64, range: TextRange },
+
+    #[error("Does not match any of the allowed schemas")]
+    NoMatch { range: TextRange },
+
+    #[error("Matches {count} of the schemas, but must match exactly one")]
+    AmbiguousMatch { count: usize, range: TextRange },
+}
+
+impl SchemaError {
+    /// The key or value that the schema does not allow.
+    pub fn range(&self) -> TextRange {
+        match self {
+            SchemaError::Type { range, .. }
+            | SchemaError::Required { range, .. }
+            | SchemaError::AdditionalProperty { range, .. }
+            | SchemaError::Forbidden { range }
+            | SchemaError::Enum { range, .. }
+            | SchemaError::Pattern { range, .. }
+            | SchemaError::TooSmall { range, .. }
+            | SchemaError::TooLarge { range, .. }
+            | SchemaError::TooShort { range, .. }
+            | SchemaError::TooLong { range, .. }
+            | SchemaError::TooFewItems { range, .. }
+            | SchemaError::TooManyItems { range, .. }
+            | SchemaError::NoMatch { range }
+            | SchemaError::AmbiguousMatch { range, .. } => *range,
+        }
+    }
+
+    /// A stable identifier for the kind of error, reported as the diagnostic code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SchemaError::Type { .. } => "schema-type",
+            SchemaError::Required { .. } => "schema-required",
+            SchemaError::AdditionalProperty { .. } | SchemaError::Forbidden { .. } => {
+                "schema-not-allowed"
+            }
+            SchemaError::Enum { .. } => "schema-enum",
+            SchemaError::Pattern { .. } => "schema-pattern",
+            SchemaError::TooSmall { .. } | SchemaError::TooLarge { .. } => "schema-range",
+            SchemaError::TooShort { .. } | SchemaError::TooLong { .. } => "schema-length",
+            SchemaError::TooFewItems { .. } | SchemaError::TooManyItems { .. } => "schema-items",
+            SchemaError::NoMatch { .. } | SchemaError::AmbiguousMatch { .. } => "schema-no-match",
+        }
+    }
+}
+
+impl Schema {
+    /// Checks a document, whose root table is reported at the start of the document.
+    pub fn validate(&self, root: &Table) -> Vec<SchemaError> {
+        let mut validator = Validator {
+            schema: self,
+            errors: Vec::new(),
+        };
+        let range = TextRange::empty(0.into());
+        validator.check(&self.root, &ValueKind::Table(root.clone()), range, 0);
+        validator.errors
+    }
+}
+
+struct Validator<'s> {
+    schema: &'s Schema,
+    errors: Vec<SchemaError>,
+}
+
+impl<'s> Validator<'s> {
+    /// Checks a value, which is reported at `range`, against `schema`. `refs` counts the
+    /// `$ref`s that led to `schema` without going deeper into the value.
+    fn check(&mut self, schema: &'s Json, kind: &ValueKind, range: TextRange, refs: u32) {
+        let object = match schema {
+            Json::Bool(false) => {
+                self.errors.push(SchemaError::Forbidden { range });
+                return;
+            }
+            Json::Object(object) => object,
+            _ => return,
+        };
+        // Values with syntax errors are reported by the parser already.
+        if let ValueKind::Invalid = kind {
+            return;
+        }
+        if let Some(target) = self.schema.target(schema) {
+            if refs < MAX_REFS {
+                self.check(target, kind, range, refs + 1);
+            }
+        }
+
+        let types = types(schema);
+        if !types.is_empty() && !types.iter().any(|name| has_type(kind, name)) {
+            self.errors.push(SchemaError::Type {
+                expected: types.into_iter().map(type_name).collect(),
+                found: kind.type_name(),
+                range,
+            });
+            // The other keywords are meant for values of the right type.
+            return;
+        }
+
+        if let Some(allowed) = object.get("enum").and_then(Json::as_array) {
+            if !allowed.iter().any(|value| equals(value, kind)) {
+                self.errors.push(SchemaError::Enum {
+                    allowed: allowed.iter().map(Json::to_string).collect(),
+                    range,
+                });
+            }
+        }
+        if let Some(constant) = object.get("const") {
+            if !equals(constant, kind) {
+                self.errors.push(SchemaError::Enum {
+                    allowed: vec![constant.to_string()],
+                    range,
+                });
+            }
+        }
+
+        for schema in array(object, "allOf") {
+            self.check(schema, kind, range, refs);
+        }
+        if let Some(Json::Array(branches)) = object.get("anyOf") {
+            if self.matching(branches, kind, range, refs) == 0 {
+                self.errors.push(SchemaError::NoMatch { range });
+            }
+        }
+        if let Some(Json::Array(branches)) = object.get("oneOf") {
+            match self.matching(branches, kind, range, refs) {
+                0 => self.errors.push(SchemaError::NoMatch { range }),
+                1 => (),
+                count => self
+                    .errors
+                    .push(SchemaError::AmbiguousMatch { count, range }),
+            }
+        }
+
+        match kind {
+            ValueKind::String(text)
+            | ValueKind::OffsetDateTime(text)
+            | ValueKind::LocalDateTime(text)
+            | ValueKind::LocalDate(text)
+            | ValueKind::LocalTime(text) => self.check_string(object, text, range),
+            ValueKind::Integer(value) => self.check_number(object, *value as f64, range),
+            ValueKind::Float(value) => self.check_number(object, *value, range),
+            ValueKind::Array(array) => self.check_array(object, array, range),
+            ValueKind::Table(table) => self.check_table(object, table, range),
+            ValueKind::Boolean(_) | ValueKind::Invalid => (),
+        }
+    }
+
+    /// How many of `branches` a value matches, without reporting anything.
+    fn matching(
+        &self,
+        branches: &'s [Json],
+        kind: &ValueKind,
+        range: TextRange,
+        refs: u32,
+    ) -> usize {
+        let matches = |branch| {
+            let mut validator = Validator {
+                schema: self.schema,
+                errors: Vec::new(),
+            };
+            validator.check(branch, kind, range, refs);
+            validator.errors.is_empty()
+        };
+        branches.iter().filter(|branch| matches(branch)).count()
+    }
+
+    fn check_string(&mut self, schema: &Map<String, Json>, text: &str, range: TextRange) {
+        let len = text.chars().count() as u64;
+        if let Some(limit) = schema.get("minLength").and_then(Json::as_u64) {
+            if len < limit {
+                self.errors.push(SchemaError::TooShort { limit, range });
+            }
+        }
+        if let Some(limit) = schema.get("maxLength").and_then(Json::as_u64) {
+            if len > limit {
+                self.errors.push(SchemaError::TooLong { limit, range });
+            }
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(Json::as_str) {
+            let regex = self.schema.regexes.get(pattern);
+            if regex.is_some_and(|regex| !regex.is_match(text)) {
+                self.errors.push(SchemaError::Pattern {
+                    value: text.to_string(),
+                    pattern: pattern.to_string(),
+                    range,
+                });
+            }
+        }
+    }
+
+    fn check_number(&mut self, schema: &Map<String, Json>, value: f64, range: TextRange) {
+        let limit = |name| schema.get(name).and_then(Json::as_f64);
+        if let Some(limit) = limit("minimum").filter(|&limit| value < limit) {
+            self.errors.push(SchemaError::TooSmall {
+                limit,
+                exclusive: false,
+                range,
+            });
+        }
+        if let Some(limit) = limit("exclusiveMinimum").filter(|&limit| value <= limit) {
+            self.errors.push(SchemaError::TooSmall {
+                limit,
+                exclusive: true,
+                range,
+            });
+        }
+        if let Some(limit) = limit("maximum").filter(|&limit| value > limit) {
+            self.errors.push(SchemaError::TooLarge {
+                limit,
+                exclusive: false,
+                range,
+            });
+        }
+        if let Some(limit) = limit("exclusiveMaximum").filter(|&limit| value >= limit) {
+            self.errors.push(SchemaError::TooLarge {
+                limit,
+                exclusive: true,
+                range,
+            });
+        }
+    }
+
+    fn check_array(&mut self, schema: &'s Map<String, Json>, array: &Array, range: TextRange) {
+        let len = array.values.len() as u64;
+        if let Some(limit) = schema.get("minItems").and_then(Json::as_u64) {
+            if len < limit {
+                self.errors.push(SchemaError::TooFewItems { limit, range });
+            }
+        }
+        if let Some(limit) = schema.get("maxItems").and_then(Json::as_u64) {
+            if len > limit {
+                self.errors.push(SchemaError::TooManyItems { limit, range });
+            }
+        }
+        for (i, value) in array.values.iter().enumerate() {
+            let items = match schema.get("items") {
+                Some(Json::Array(items)) => items.get(i),
+                items => items,
+            };
+            if let Some(items) = items {
+                self.check(items, &value.kind, value.range, 0);
+            }
+        }
+    }
+
+    fn check_table(&mut self, schema: &'s Map<String, Json>, table: &Table, range: TextRange) {
+        let properties = schema.get("properties").and_then(Json::as_object);
+        let patterns = schema.get("patternProperties").and_then(Json::as_object);
+        for entry in &table.entries {
+            let range = entry_range(entry);
+            let mut known = false;
+            if let Some(property) = properties.and_then(|properties| properties.get(&entry.key)) {
+                known = true;
+                self.check(property, &entry.value.kind, range, 0);
+            }
+            for (pattern, property) in patterns.into_iter().flatten() {
+                let regex = self.schema.regexes.get(pattern);
+                if regex.is_some_and(|regex| regex.is_match(&entry.key)) {
+                    known = true;
+                    self.check(property, &entry.value.kind, range, 0);
+                }
+            }
+            match schema.get("additionalProperties") {
+                _ if known => (),
+                Some(Json::Bool(false)) => self.errors.push(SchemaError::AdditionalProperty {
+                    key: entry.key.clone(),
+                    range: entry.key_range,
+                }),
+                Some(additional) => self.check(additional, &entry.value.kind, range, 0),
+                None => (),
+            }
+        }
+
+        for key in array(schema, "required").filter_map(Json::as_str) {
+            if table.get(key).is_none() {
+                self.errors.push(SchemaError::Required {
+                    key: key.to_string(),
+                    range,
+                });
+            }
+        }
+    }
+}
+
+fn array<'s>(schema: &'s Map<String, Json>, name: &str) -> impl Iterator<Item = &'s Json> {
+    schema
+        .get(name)
+        .and_then(Json::as_array)
+        .into_iter()
+        .flatten()
+}
+
+/// Where to report an entry: tables that are defined over several lines are reported at
+/// their key, and everything else at its value.
+fn entry_range(entry: &Entry) -> TextRange {
+    match &entry.value.kind {
+        ValueKind::Table(Table {
+            kind: TableKind::Inline,
+            ..
+        })
+        | ValueKind::Array(Array {
+            kind: ArrayKind::Static,
+            ..
+        }) => entry.value.range,
+        ValueKind::Table(_) | ValueKind::Array(_) => entry.key_range,
+        _ => entry.value.range,
+    }
+}
+
+pub(super) fn has_type(kind: &ValueKind, name: &str) -> bool {
+    matches!(
+        (name, kind),
+        ("string", ValueKind::String(_))
+            | ("string", ValueKind::OffsetDateTime(_))
+            | ("string", ValueKind::LocalDateTime(_))
+            | ("string", ValueKind::LocalDate(_))
+            | ("string", ValueKind::LocalTime(_))
+            | ("integer", ValueKind::Integer(_))
+            | ("number", ValueKind::Integer(_))
+            | ("number", ValueKind::Float(_))
+            | ("boolean", ValueKind::Boolean(_))
+            | ("array", ValueKind::Array(_))
+            | ("object", ValueKind::Table(_))
+    )
+}
+
+fn equals(json: &Json, kind: &ValueKind) -> bool {
+    match (json, kind) {
+        (Json::String(json), ValueKind::String(text))
+        | (Json::String(json), ValueKind::OffsetDateTime(text))
+        | (Json::String(json), ValueKind::LocalDateTime(text))
+        | (Json::String(json), ValueKind::LocalDate(text))
+        | (Json::String(json), ValueKind::LocalTime(text)) => json == text,
+        (Json::Number(json), ValueKind::Integer(value)) => {
+            json.as_i64() == Some(*value) || json.as_f64() == Some(*value as f64)
+        }
+        (Json::Number(json), ValueKind::Float(value)) => json.as_f64() == Some(*value),
+        (Json::Bool(json), ValueKind::Boolean(value)) => json == value,
+        (Json::Array(json), ValueKind::Array(array)) => {
+            json.len() == array.values.len()
+                && json
+                    .iter()
+                    .zip(&array.values)
+                    .all(|(json, value)| equals(json, &value.kind))
+        }
+        (Json::Object(json), ValueKind::Table(table)) => {
+            json.len() == table.entries.len()
+                && table.entries.iter().all(|entry| {
+                    json.get(&entry.key)
+                        .is_some_and(|json| equals(json, &entry.value.kind))
+                })
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, fs, path::PathBuf};
+
+    use expect_test::expect_file;
+
+    use super::*;
+    use crate::{parser::parse, semantic};
+
+    #[test]
+    fn validate_dir() {
+        let dir: PathBuf = [env!("CARGO_MANIFEST_DIR"), "test_data", "schema"]
+            .iter()
+            .collect();
+        let schema = fs::read_to_string(dir.join("schema.json")).expect("Failed to read schema");
+        let schema = Schema::new(serde_json::from_str(&schema).expect("Invalid schema"));
+        for entry in dir.read_dir().expect("Failed to read dir") {
+            let path = entry.expect("Failed to read entry").path();
+            if path.extension() != Some(OsStr::new("toml")) {
+                continue;
+            }
+            let input = fs::read_to_string(&path).expect("Failed to read to string");
+            let document = semantic::lower(&parse(&input).root());
+            let out: String = schema
+                .validate(&document.root)
+                .iter()
+                .map(|err| {
+                    format!(
+                        "{} @{:?} {:?}: {}\n",
+                        err.code(),
+                        err.range(),
+                        &input[err.range()],
+                        err
+                    )
+                })
+                .collect();
+            expect_file![path.with_extension("expect")].assert_eq(&out);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Growing the selection from the cursor, for `textDocument/selectionRange`.
+
+use lsp_types::SelectionRange;
+use rowan::{TextRange, TextSize};
+
+use crate::{
+    lexer::SyntaxKind::{self, *},
+    line_index::LineIndex,
+    parser::{SyntaxNode, AST},
+};
+
+/// Returns the ranges around `offset`, the smallest first: the contents of a string
+/// without its quotes, the token, and then every node it is in whose range is larger
+/// than the one before, from values and key/value pairs up to the section and the document.
+pub fn selection_ranges(ast: &AST, offset: TextSize) -> Vec<TextRange> {
+    let node = ast.node();
+    let tokens = node.token_at_offset(offset);
+    let token = match tokens.clone().right_biased() {
+        Some(token) if !token.kind().is_trivia() && token.kind() != Newline => token,
+        _ => match tokens.left_biased() {
+            Some(token) => token,
+            None => return vec![node.text_range()],
+        },
+    };
+
+    let mut ranges = Vec::new();
+    let mut push = |range: TextRange| {
+        if ranges.last().is_none_or(|last: &TextRange| range != *last) {
+            ranges.push(range);
+        }
+    };
+    if let Some(contents) = string_contents(token.kind(), token.text_range()) {
+        if !contents.is_empty() {
+            push(contents);
+        }
+    }
+    if !matches!(token.kind(), Whitespace | Newline) {
+        push(token.text_range());
+    }
+    for node in token.parent().ancestors() {
+        push(trimmed(&node));
+    }
+    ranges
+}
+
+/// The range of a string token without its quotes.
+fn string_contents(kind: SyntaxKind, range: TextRange) -> Option<TextRange> {
+    let quotes = match kind {
+        BasicString | LiteralString => 1,
+        MultiLineBasicString | MultiLineLiteralString => 3,
+        _ => return None,
+    };
+    let quotes = TextSize::from(quotes);
+    if range.len() < quotes + quotes {
+        return None;
+    }
+    Some(TextRange::new(range.start() + quotes, range.end() - quotes))
+}
+
+/// The range of a node without the blank lines at its end, which sections have before the
+/// next header.
+fn trimmed(node: &SyntaxNode) -> TextRange {
+    let end = node
+        .descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .filter(|token| !matches!(token.kind(), Whitespace | Newline))
+        .last();
+    match end {
+        Some(end) if node.kind() != Root => {
+            TextRange::new(node.text_range().start(), end.text_range().end())
+        }
+        _ => node.text_range(),
+    }
+}
+
+/// Nests ranges, the smallest first, into the linked form of the protocol.
+pub fn to_lsp(ranges: &[TextRange], line_index: &LineIndex) -> SelectionRange {
+    let mut ranges = ranges.iter().rev();
+    let outermost = ranges.next().copied().unwrap_or_default();
+    let mut selection = SelectionRange {
+        range: line_index.range(outermost),
+        parent: None,
+    };
+    for range in ranges {
+        selection = SelectionRange {
+            range: line_index.range(*range),
+            parent: Some(Box::new(selection)),
+        };
+    }
+    selection
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::{expect, Expect};
+
+    use super::*;
+    use crate::parser::parse;
+
+    /// Lists the selections at the `$0` in `input`.
+    fn check(input: &str, expect: Expect) {
+        let offset = input.find("$0").expect("no cursor in input");
+        let input = input.replace("$0", "");
+        let ast = parse(&input);
+        let out: String = selection_ranges(&ast, (offset as u32).into())
+            .into_iter()
+            .map(|range| format!("{:?}\n", &input[range]))
+            .collect();
+        expect.assert_eq(&out);
+    }
+
+    #[test]
+    fn selections() {
+        let input = r#"title = "fruit"
+
+[fruit]
+colors = ["red", "green"]
+size.max = 3
+
+[other]
+"#;
+        check(
+            &input.replace("\"gr", "\"g$0r"),
+            expect![[r#"
+                "green"
+                "\"green\""
+                "[\"red\", \"green\"]"
+                "colors = [\"red\", \"green\"]"
+                "[fruit]\ncolors = [\"red\", \"green\"]\nsize.max = 3"
+                "title = \"fruit\"\n\n[fruit]\ncolors = [\"red\", \"green\"]\nsize.max = 3\n\n[other]\n"
+            "#]],
+        );
+        check(
+            &input.replace("max", "m$0ax"),
+            expect![[r#"
+                "max"
+                "size.max"
+                "size.max = 3"
+                "[fruit]\ncolors = [\"red\", \"green\"]\nsize.max = 3"
+                "title = \"fruit\"\n\n[fruit]\ncolors = [\"red\", \"green\"]\nsize.max = 3\n\n[other]\n"
+            "#]],
+        );
+        check(
+            &input.replace("[other", "[$0other"),
+            expect![[r#"
+                "other"
+                "[other]"
+                "title = \"fruit\"\n\n[fruit]\ncolors = [\"red\", \"green\"]\nsize.max = 3\n\n[other]\n"
+            "#]],
+        );
+    }
+}
@@ -0,0 +1,808 @@
+//! Lowers the syntax tree into the tables, arrays and scalars the document stands for,
+//! following TOML's rules for dotted keys, headers and arrays of tables. Every part of the
+//! result remembers the range that defined it.
+
+use std::fmt;
+
+use rowan::{TextRange, TextSize};
+use thiserror::Error;
+
+use crate::lexer::SyntaxKind;
+use crate::parser::{
+    ast::{self, AstNode},
+    float_value, integer_value, string_value, SyntaxToken,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableKind {
+    /// The document itself.
+    Root,
+    /// Created by a `[table]` header, or as an element of an `[[array]]`.
+    Header,
+    /// Created as a parent of a dotted key, like `a` in `a.b = 1`.
+    Dotted,
+    /// Created as a parent of a header, like `a` in `[a.b]`. It may still be defined by a
+    /// header of its own later.
+    Implicit,
+    /// An inline table, which cannot be extended after its closing brace.
+    Inline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayKind {
+    /// An array value, which cannot be extended after its closing bracket.
+    Static,
+    /// Created by `[[array]]` headers, which each append a table.
+    OfTables,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub kind: TableKind,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Array {
+    pub kind: ArrayKind,
+    pub values: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    /// The key segment that defined this entry.
+    pub key_range: TextRange,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Value {
+    pub kind: ValueKind,
+    /// The value itself, or the whole section for tables created by a header.
+    pub range: TextRange,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueKind {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    OffsetDateTime(String),
+    LocalDateTime(String),
+    LocalDate(String),
+    LocalTime(String),
+    Array(Array),
+    Table(Table),
+    /// A value that has a syntax error, which is reported by the parser.
+    Invalid,
+}
+
+impl Table {
+    fn new(kind: TableKind) -> Table {
+        Table {
+            kind,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.key == key)
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.key == key)
+    }
+
+    /// Returns the value at `path` below this table.
+    pub fn lookup(&self, path: &[PathSegment]) -> Option<&Value> {
+        let (first, rest) = path.split_first()?;
+        let mut value = match first {
+            PathSegment::Key(key) => &self.get(key)?.value,
+            PathSegment::Index(_) => return None,
+        };
+        for segment in rest {
+            value = match (segment, &value.kind) {
+                (PathSegment::Key(key), ValueKind::Table(table)) => &table.get(key)?.value,
+                (PathSegment::Index(i), ValueKind::Array(array)) => array.values.get(*i)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Resolves the key of a header that starts at `offset` to the path of the table it
+    /// stands for. Arrays of tables stand for their last element that starts before the
+    /// header, as it is the one the header extends or appends.
+    pub fn resolve_header(&self, names: &[String], offset: TextSize) -> Path {
+        let mut path = Vec::with_capacity(names.len());
+        let mut table = Some(self);
+        for name in names {
+            path.push(PathSegment::Key(name.clone()));
+            let value = table.and_then(|table| table.get(name)).map(|entry| &entry.value);
+            table = match value.map(|value| &value.kind) {
+                Some(ValueKind::Table(table)) => Some(table),
+                Some(ValueKind::Array(Array {
+                    kind: ArrayKind::OfTables,
+                    values,
+                })) => values
+                    .iter()
+                    .rposition(|value| value.range.start() <= offset)
+                    .and_then(|i| {
+                        path.push(PathSegment::Index(i));
+                        match &values[i].kind {
+                            ValueKind::Table(table) => Some(table),
+                            _ => None,
+                        }
+                    }),
+                _ => None,
+            };
+        }
+        Path(path)
+    }
+}
+
+/// One step from a table or array to a value in it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// The fully qualified location of a value, like `dependencies.serde.features[2]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Path(pub Vec<PathSegment>);
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) => {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    let bare = !key.is_empty()
+                        && key
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                    if bare {
+                        f.write_str(key)?;
+                    } else {
+                        write!(f, "{:?}", key)?;
+                    }
+                }
+                PathSegment::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ValueKind {
+    /// The name of this kind of value, as the TOML specification calls it.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ValueKind::String(_) => "string",
+            ValueKind::Integer(_) => "integer",
+            ValueKind::Float(_) => "float",
+            ValueKind::Boolean(_) => "boolean",
+            ValueKind::OffsetDateTime(_) => "offset date-time",
+            ValueKind::LocalDateTime(_) => "local date-time",
+            ValueKind::LocalDate(_) => "local date",
+            ValueKind::LocalTime(_) => "local time",
+            ValueKind::Array(_) => "array",
+            ValueKind::Table(_) => "table",
+            ValueKind::Invalid => "invalid",
+        }
+    }
+
+    fn as_table_mut(&mut self) -> Option<&mut Table> {
+        match self {
+            ValueKind::Table(table) => Some(table),
+            ValueKind::Array(Array {
+                kind: ArrayKind::OfTables,
+                values,
+            }) => values.last_mut()?.kind.as_table_mut(),
+            _ => None,
+        }
+    }
+}
+
+/// The value tree of a document, together with the definitions that broke TOML's rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub root: Table,
+    pub errors: Vec<SemanticError>,
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum SemanticError {
+    #[error("Duplicate key `{key}`")]
+    DuplicateKey {
+        key: String,
+        range: TextRange,
+        first: TextRange,
+    },
+
+    #[error("Table `{key}` is already defined")]
+    DuplicateTable {
+        key: String,
+        range: TextRange,
+        first: TextRange,
+    },
+
+    #[error("Inline table `{key}` cannot be extended")]
+    ExtendInlineTable {
+        key: String,
+        range: TextRange,
+        first: TextRange,
+    },
+
+    #[error("Array `{key}` is not an array of tables and cannot be extended")]
+    ExtendStaticArray {
+        key: String,
+        range: TextRange,
+        first: TextRange,
+    },
+}
+
+impl SemanticError {
+    /// The definition that broke the rules.
+    pub fn range(&self) -> TextRange {
+        match self {
+            SemanticError::DuplicateKey { range, .. }
+            | SemanticError::DuplicateTable { range, .. }
+            | SemanticError::ExtendInlineTable { range, .. }
+            | SemanticError::ExtendStaticArray { range, .. } => *range,
+        }
+    }
+
+    /// A stable identifier for the kind of error, reported as the diagnostic code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SemanticError::DuplicateKey { .. } => "duplicate-key",
+            SemanticError::DuplicateTable { .. } => "duplicate-table",
+            SemanticError::ExtendInlineTable { .. } => "extend-inline-table",
+            SemanticError::ExtendStaticArray { .. } => "extend-static-array",
+        }
+    }
+
+    /// The earlier definition of the same key.
+    pub fn first_definition(&self) -> TextRange {
+        match self {
+            SemanticError::DuplicateKey { first, .. }
+            | SemanticError::DuplicateTable { first, .. }
+            | SemanticError::ExtendInlineTable { first, .. }
+            | SemanticError::ExtendStaticArray { first, .. } => *first,
+        }
+    }
+}
+
+/// Describes why `range` cannot define or, if `extending`, reach into `first`.
+fn conflict(first: &Entry, range: TextRange, extending: bool) -> SemanticError {
+    let key = first.key.clone();
+    let kind = &first.value.kind;
+    let first = first.key_range;
+    match kind {
+        ValueKind::Table(Table {
+            kind: TableKind::Inline,
+            ..
+        }) if extending => SemanticError::ExtendInlineTable { key, range, first },
+        ValueKind::Array(Array {
+            kind: ArrayKind::Static,
+            ..
+        }) if extending => SemanticError::ExtendStaticArray { key, range, first },
+        ValueKind::Table(_)
+        | ValueKind::Array(Array {
+            kind: ArrayKind::OfTables,
+            ..
+        }) => SemanticError::DuplicateTable { key, range, first },
+        _ => SemanticError::DuplicateKey { key, range, first },
+    }
+}
+
+/// Builds the value tree of a document. Definitions that break TOML's rules are reported and
+/// skipped, so that the first definition always wins.
+pub fn lower(root: &ast::Root) -> Document {
+    let mut document = Table::new(TableKind::Root);
+    let mut errors = Vec::new();
+
+    for entry in root.entries() {
+        let (path, entries): (_, Vec<_>) = match entry {
+            ast::Entry::KeyValue(kv) => {
+                insert_key_value(&mut document, &kv, &mut errors);
+                continue;
+            }
+            ast::Entry::Table(header) => {
+                let section = header.syntax().text_range();
+                let path = header
+                    .key()
+                    .and_then(|key| key_names(&key))
+                    .filter(|path| define_table(&mut document, path, section, &mut errors));
+                (path, header.entries().collect())
+            }
+            ast::Entry::ArrayOfTables(header) => {
+                let section = header.syntax().text_range();
+                let path = header
+                    .key()
+                    .and_then(|key| key_names(&key))
+                    .filter(|path| append_table(&mut document, path, section, &mut errors));
+                (path, header.entries().collect())
+            }
+        };
+
+        let table = path.and_then(|path| table_at(&mut document, &path));
+        if let Some(table) = table {
+            for kv in entries {
+                insert_key_value(table, &kv, &mut errors);
+            }
+        }
+    }
+
+    Document {
+        root: document,
+        errors,
+    }
+}
+
+fn key_names(key: &ast::Key) -> Option<Vec<(String, TextRange)>> {
+    key.segments()
+        .map(|segment| Some((segment.name()?, segment.text_range())))
+        .collect()
+}
+
+/// Returns the table at `path`, where arrays of tables stand for their last element.
+fn table_at<'t>(table: &'t mut Table, path: &[(String, TextRange)]) -> Option<&'t mut Table> {
+    path.iter().try_fold(table, |table, (name, _)| {
+        let i = table.position(name)?;
+        table.entries[i].value.kind.as_table_mut()
+    })
+}
+
+/// Returns the table called `name` in `table`, creating it with the given kind if it does
+/// not exist yet. Reports an error and returns `None` if `name` is something else than a
+/// table, or a table that `kind` is not allowed to extend.
+fn child_table<'t>(
+    table: &'t mut Table,
+    name: &str,
+    range: TextRange,
+    kind: TableKind,
+    errors: &mut Vec<SemanticError>,
+) -> Option<&'t mut Table> {
+    let i = match table.position(name) {
+        Some(i) => i,
+        None => {
+            table.entries.push(Entry {
+                key: name.to_string(),
+                key_range: range,
+                value: Value {
+                    kind: ValueKind::Table(Table::new(kind)),
+                    range,
+                },
+            });
+            table.entries.len() - 1
+        }
+    };
+
+    let error = conflict(&table.entries[i], range, true);
+    let child = match table.entries[i].value.kind.as_table_mut() {
+        Some(child) => child,
+        None => {
+            errors.push(error);
+            return None;
+        }
+    };
+    match (child.kind, kind) {
+        // Dotted keys cannot reach into a table that was defined by a header either.
+        (TableKind::Inline, _) | (TableKind::Header, TableKind::Dotted) => {
+            errors.push(error);
+            None
+        }
+        (TableKind::Implicit, TableKind::Dotted) => {
+            child.kind = TableKind::Dotted;
+            Some(child)
+        }
+        _ => Some(child),
+    }
+}
+
+/// Walks to the parent of the last segment of `path`, creating implicit tables on the way.
+fn parent_table<'t>(
+    document: &'t mut Table,
+    path: &'t [(String, TextRange)],
+    errors: &mut Vec<SemanticError>,
+) -> Option<(&'t mut Table, &'t str, TextRange)> {
+    let ((name, range), parents) = path.split_last()?;
+    let parent = parents.iter().try_fold(document, |table, (name, range)| {
+        child_table(table, name, *range, TableKind::Implicit, errors)
+    })?;
+    Some((parent, name, *range))
+}
+
+/// Defines the table of a `[table]` header. Returns whether the definition is valid.
+fn define_table(
+    document: &mut Table,
+    path: &[(String, TextRange)],
+    section: TextRange,
+    errors: &mut Vec<SemanticError>,
+) -> bool {
+    let (parent, name, range) = match parent_table(document, path, errors) {
+        Some(it) => it,
+        None => return false,
+    };
+
+    match parent.position(name) {
+        None => {
+            parent.entries.push(Entry {
+                key: name.to_string(),
+                key_range: range,
+                value: Value {
+                    kind: ValueKind::Table(Table::new(TableKind::Header)),
+                    range: section,
+                },
+            });
+            true
+        }
+        Some(i) => {
+            let entry = &mut parent.entries[i];
+            match &mut entry.value.kind {
+                ValueKind::Table(table) if table.kind == TableKind::Implicit => {
+                    table.kind = TableKind::Header;
+                    entry.key_range = range;
+                    entry.value.range = section;
+                    true
+                }
+                _ => {
+                    errors.push(conflict(entry, range, false));
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Appends a table to the array of an `[[array]]` header. Returns whether that is valid.
+fn append_table(
+    document: &mut Table,
+    path: &[(String, TextRange)],
+    section: TextRange,
+    errors: &mut Vec<SemanticError>,
+) -> bool {
+    let (parent, name, range) = match parent_table(document, path, errors) {
+        Some(it) => it,
+        None => return false,
+    };
+
+    let element = Value {
+        kind: ValueKind::Table(Table::new(TableKind::Header)),
+        range: section,
+    };
+    match parent.position(name) {
+        None => {
+            parent.entries.push(Entry {
+                key: name.to_string(),
+                key_range: range,
+                value: Value {
+                    kind: ValueKind::Array(Array {
+                        kind: ArrayKind::OfTables,
+                        values: vec![element],
+                    }),
+                    range: section,
+                },
+            });
+            true
+        }
+        Some(i) => {
+            let error = conflict(&parent.entries[i], range, true);
+            match &mut parent.entries[i].value.kind {
+                ValueKind::Array(Array {
+                    kind: ArrayKind::OfTables,
+                    values,
+                }) => {
+                    values.push(element);
+                    true
+                }
+                _ => {
+                    errors.push(error);
+                    false
+                }
+            }
+        }
+    }
+}
+
+fn insert_key_value(table: &mut Table, kv: &ast::KeyValue, errors: &mut Vec<SemanticError>) {
+    let path = match kv.key().and_then(|key| key_names(&key)) {
+        Some(path) => path,
+        None => return,
+    };
+    let ((name, range), parents) = match path.split_last() {
+        Some(it) => it,
+        None => return,
+    };
+
+    let parent = parents.iter().try_fold(table, |table, (name, range)| {
+        child_table(table, name, *range, TableKind::Dotted, errors)
+    });
+    let parent = match parent {
+        Some(parent) => parent,
+        None => return,
+    };
+    if let Some(first) = parent.get(name) {
+        errors.push(SemanticError::DuplicateKey {
+            key: name.clone(),
+            range: *range,
+            first: first.key_range,
+        });
+        return;
+    }
+
+    let value = match kv.value() {
+        Some(value) => lower_value(&value, errors),
+        None => Value {
+            kind: ValueKind::Invalid,
+            range: *range,
+        },
+    };
+    parent.entries.push(Entry {
+        key: name.clone(),
+        key_range: *range,
+        value,
+    });
+}
+
+fn lower_value(value: &ast::Value, errors: &mut Vec<SemanticError>) -> Value {
+    let kind = match value {
+        ast::Value::Scalar(token) => lower_scalar(token),
+        ast::Value::Array(array) => ValueKind::Array(Array {
+            kind: ArrayKind::Static,
+            values: array
+                .values()
+                .map(|value| lower_value(&value, errors))
+                .collect(),
+        }),
+        ast::Value::InlineTable(inline) => {
+            let mut table = Table::new(TableKind::Inline);
+            for kv in inline.entries() {
+                insert_key_value(&mut table, &kv, errors);
+            }
+            ValueKind::Table(table)
+        }
+    };
+    Value {
+        kind,
+        range: value.text_range(),
+    }
+}
+
+fn lower_scalar(token: &SyntaxToken) -> ValueKind {
+    let text: &str = token.text();
+    match token.kind() {
+        SyntaxKind::Integer => integer_value(text)
+            .map(ValueKind::Integer)
+            .unwrap_or(ValueKind::Invalid),
+        SyntaxKind::Float => float_value(text)
+            .map(ValueKind::Float)
+            .unwrap_or(ValueKind::Invalid),
+        SyntaxKind::True => ValueKind::Boolean(true),
+        SyntaxKind::False => ValueKind::Boolean(false),
+        SyntaxKind::OffsetDateTime => ValueKind::OffsetDateTime(text.to_string()),
+        SyntaxKind::LocalDateTime => ValueKind::LocalDateTime(text.to_string()),
+        SyntaxKind::LocalDate => ValueKind::LocalDate(text.to_string()),
+        SyntaxKind::LocalTime => ValueKind::LocalTime(text.to_string()),
+        kind if kind.is_string() => ValueKind::String(string_value(
+            kind,
+            text,
+            token.text_range().start(),
+            |_| (),
+        )),
+        _ => ValueKind::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use expect_test::{expect, Expect};
+
+    use super::*;
+    use crate::parser::parse;
+
+    fn dump_value(out: &mut String, value: &Value, indent: usize) {
+        match &value.kind {
+            ValueKind::Table(table) => {
+                writeln!(out, "{:?} table @{:?}", table.kind, value.range).unwrap();
+                dump_table(out, table, indent + 1);
+            }
+            ValueKind::Array(array) => {
+                writeln!(out, "{:?} array @{:?}", array.kind, value.range).unwrap();
+                for value in &array.values {
+                    write!(out, "{:indent$}- ", "", indent = (indent + 1) * 2).unwrap();
+                    dump_value(out, value, indent + 1);
+                }
+            }
+            kind => writeln!(out, "{:?} @{:?}", kind, value.range).unwrap(),
+        }
+    }
+
+    fn dump_table(out: &mut String, table: &Table, indent: usize) {
+        for entry in &table.entries {
+            write!(
+                out,
+                "{:indent$}{} @{:?} = ",
+                "",
+                entry.key,
+                entry.key_range,
+                indent = indent * 2
+            )
+            .unwrap();
+            dump_value(out, &entry.value, indent);
+        }
+    }
+
+    fn check(input: &str, expected: Expect) {
+        let document = lower(&parse(input).root());
+        let mut actual = String::new();
+        for error in &document.errors {
+            writeln!(
+                actual,
+                "error @{:?}: {} (first defined @{:?})",
+                error.range(),
+                error,
+                error.first_definition()
+            )
+            .unwrap();
+        }
+        dump_table(&mut actual, &document.root, 0);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn dotted_keys_and_headers() {
+        check(
+            r#"title = "x"
+a.b = 1
+[server.http]
+port = 8080
+[server]
+name = 'main'
+"#,
+            expect![[r#"
+                title @0..5 = String("x") @8..11
+                a @12..13 = Dotted table @12..13
+                  b @14..15 = Integer(1) @18..19
+                server @47..53 = Header table @46..69
+                  http @28..32 = Header table @20..46
+                    port @34..38 = Integer(8080) @41..45
+                  name @55..59 = String("main") @62..68
+            "#]],
+        );
+    }
+
+    #[test]
+    fn arrays_of_tables() {
+        check(
+            r#"[[products]]
+name = "Hammer"
+[[products]]
+[[products.variants]]
+color = "red"
+[[products]]
+name = "Nail"
+"#,
+            expect![[r#"
+                products @2..10 = OfTables array @0..29
+                  - Header table @0..29
+                    name @13..17 = String("Hammer") @20..28
+                  - Header table @29..42
+                    variants @53..61 = OfTables array @42..78
+                      - Header table @42..78
+                        color @64..69 = String("red") @72..77
+                  - Header table @78..105
+                    name @91..95 = String("Nail") @98..104
+            "#]],
+        );
+    }
+
+    #[test]
+    fn values() {
+        check(
+            r#"point = { x = 1.5, y.z = true }
+list = [1979-05-27, 07:32:00, "a\tb"]
+"#,
+            expect![[r#"
+                point @0..5 = Inline table @8..31
+                  x @10..11 = Float(1.5) @14..17
+                  y @19..20 = Dotted table @19..20
+                    z @21..22 = Boolean(true) @25..29
+                list @32..36 = Static array @39..69
+                  - LocalDate("1979-05-27") @40..50
+                  - LocalTime("07:32:00") @52..60
+                  - String("a\tb") @62..68
+            "#]],
+        );
+    }
+
+    #[test]
+    fn first_definition_wins() {
+        check(
+            r#"a = 1
+a = 2
+b = { c = 1 }
+b.d = 2
+[b]
+[[a]]
+"#,
+            expect![[r#"
+                error @6..7: Duplicate key `a` (first defined @0..1)
+                error @26..27: Inline table `b` cannot be extended (first defined @12..13)
+                error @35..36: Table `b` is already defined (first defined @12..13)
+                error @40..41: Duplicate key `a` (first defined @0..1)
+                a @0..1 = Integer(1) @4..5
+                b @12..13 = Inline table @16..25
+                  c @18..19 = Integer(1) @22..23
+            "#]],
+        );
+    }
+
+    #[test]
+    fn redefinitions() {
+        let input = r#"fruit.apple.color = "red"
+fruit.apple = 1
+inline = { a = 1 }
+inline.b = 2
+static = [1]
+[[static]]
+[fruit.apple]
+[fruit]
+[fruit.apple.texture]
+[inline.c]
+[point]
+[point]
+[[point]]
+"#;
+        check(input, expect![[r#"
+            error @32..37: Duplicate key `apple` (first defined @6..11)
+            error @61..67: Inline table `inline` cannot be extended (first defined @42..48)
+            error @89..95: Array `static` is not an array of tables and cannot be extended (first defined @74..80)
+            error @105..110: Table `apple` is already defined (first defined @6..11)
+            error @113..118: Table `fruit` is already defined (first defined @0..5)
+            error @143..149: Inline table `inline` cannot be extended (first defined @42..48)
+            error @162..167: Table `point` is already defined (first defined @154..159)
+            error @171..176: Table `point` is already defined (first defined @154..159)
+            fruit @0..5 = Dotted table @0..5
+              apple @6..11 = Dotted table @6..11
+                color @12..17 = String("red") @20..25
+                texture @133..140 = Header table @120..142
+            inline @42..48 = Inline table @51..60
+              a @53..54 = Integer(1) @57..58
+            static @74..80 = Static array @83..86
+              - Integer(1) @84..85
+            point @154..159 = Header table @153..161
+        "#]]);
+    }
+
+    #[test]
+    fn paths() {
+        let input = r#"[[a]]
+[[a]]
+[a.b]
+c = [1, { "d e" = 2 }]
+[[a]]
+"#;
+        let document = lower(&parse(input).root());
+        let names = ["a".to_string(), "b".to_string()];
+        let path = document.root.resolve_header(&names, 12.into());
+        assert_eq!(path.to_string(), "a[1].b");
+
+        let mut segments = path.0;
+        segments.push(PathSegment::Key("c".into()));
+        segments.push(PathSegment::Index(1));
+        segments.push(PathSegment::Key("d e".into()));
+        assert_eq!(Path(segments.clone()).to_string(), r#"a[1].b.c[1]."d e""#);
+        let value = document.root.lookup(&segments).unwrap();
+        assert_eq!(value.kind, ValueKind::Integer(2));
+    }
+}
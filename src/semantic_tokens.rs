@@ -0,0 +1,366 @@
+//! Highlighting of keys, values and comments, for `textDocument/semanticTokens`.
+
+use lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensEdit,
+    SemanticTokensLegend,
+};
+use rowan::{TextRange, TextSize};
+
+use crate::{
+    lexer::SyntaxKind::{self, *},
+    line_index::LineIndex,
+    parser::{SyntaxToken, AST},
+};
+
+/// The token types, in the order of the legend.
+const TYPES: &[&str] = &[
+    "namespace",
+    "struct",
+    "property",
+    "string",
+    "escapeSequence",
+    "number",
+    "boolean",
+    "dateTime",
+    "comment",
+];
+
+const NAMESPACE: u32 = 0;
+const STRUCT: u32 = 1;
+const PROPERTY: u32 = 2;
+const STRING: u32 = 3;
+const ESCAPE_SEQUENCE: u32 = 4;
+const NUMBER: u32 = 5;
+const BOOLEAN: u32 = 6;
+const DATE_TIME: u32 = 7;
+const COMMENT: u32 = 8;
+
+/// The token modifiers, each a bit of the modifier set in the order of the legend.
+const MODIFIERS: &[&str] = &[
+    "literal",
+    "multiline",
+    "integer",
+    "float",
+    "date",
+    "time",
+    "offset",
+];
+
+const LITERAL: u32 = 1 << 0;
+const MULTILINE: u32 = 1 << 1;
+const INTEGER: u32 = 1 << 2;
+const FLOAT: u32 = 1 << 3;
+const DATE: u32 = 1 << 4;
+const TIME: u32 = 1 << 5;
+const OFFSET: u32 = 1 << 6;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TYPES
+            .iter()
+            .map(|&name| SemanticTokenType::new(name))
+            .collect(),
+        token_modifiers: MODIFIERS
+            .iter()
+            .map(|&name| SemanticTokenModifier::new(name))
+            .collect(),
+    }
+}
+
+/// The type and modifiers of a token of the given kind outside of keys.
+fn highlight(kind: SyntaxKind) -> Option<(u32, u32)> {
+    let highlight = match kind {
+        BasicString => (STRING, 0),
+        LiteralString => (STRING, LITERAL),
+        MultiLineBasicString => (STRING, MULTILINE),
+        MultiLineLiteralString => (STRING, LITERAL | MULTILINE),
+        Integer => (NUMBER, INTEGER),
+        Float => (NUMBER, FLOAT),
+        True | False => (BOOLEAN, 0),
+        OffsetDateTime => (DATE_TIME, DATE | TIME | OFFSET),
+        LocalDateTime => (DATE_TIME, DATE | TIME),
+        LocalDate => (DATE_TIME, DATE),
+        LocalTime => (DATE_TIME, TIME),
+        Comment => (COMMENT, 0),
+        _ => return None,
+    };
+    Some(highlight)
+}
+
+/// A token in absolute terms, before it is encoded relative to the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Highlight {
+    range: TextRange,
+    token_type: u32,
+    modifiers: u32,
+}
+
+/// Returns the tokens of `ast`, or only those that overlap `range`. Tokens never span
+/// lines, so multi-line strings are split into a token per line.
+pub fn semantic_tokens(
+    ast: &AST,
+    line_index: &LineIndex,
+    range: Option<TextRange>,
+) -> Vec<SemanticToken> {
+    let mut highlights = Vec::new();
+    for token in ast
+        .node()
+        .descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+    {
+        if range.is_some_and(|range| {
+            token.text_range().end() <= range.start() || token.text_range().start() >= range.end()
+        }) {
+            continue;
+        }
+        highlight_token(&token, &mut highlights);
+    }
+    if let Some(range) = range {
+        highlights.retain(|it| it.range.end() > range.start() && it.range.start() < range.end());
+    }
+    encode(&highlights, line_index)
+}
+
+fn highlight_token(token: &SyntaxToken, highlights: &mut Vec<Highlight>) {
+    let key = Some(token.parent())
+        .filter(|parent| parent.kind() == KeySegment)
+        .and_then(|segment| segment.parent()?.parent());
+    let (token_type, modifiers) = match &key {
+        Some(owner) => match owner.kind() {
+            TableHeader => (NAMESPACE, 0),
+            ArrayHeader => (STRUCT, 0),
+            _ => (PROPERTY, 0),
+        },
+        None => match highlight(token.kind()) {
+            Some(highlight) => highlight,
+            None => return,
+        },
+    };
+
+    let start = token.text_range().start();
+    let text = token.text().as_str();
+    let mut push = |range: TextRange, token_type, modifiers| {
+        push_lines(text, start, range, token_type, modifiers, highlights)
+    };
+    let escapes = match token.kind() {
+        BasicString | MultiLineBasicString if key.is_none() => escapes(text),
+        _ => Vec::new(),
+    };
+    let mut at = TextSize::from(0);
+    for escape in escapes {
+        push(TextRange::new(at, escape.start()), token_type, modifiers);
+        push(escape, ESCAPE_SEQUENCE, 0);
+        at = escape.end();
+    }
+    push(
+        TextRange::new(at, TextSize::of(text)),
+        token_type,
+        modifiers,
+    );
+}
+
+/// Pushes a highlight for every line of `range` within `text`, which starts at `start`.
+fn push_lines(
+    text: &str,
+    start: TextSize,
+    range: TextRange,
+    token_type: u32,
+    modifiers: u32,
+    highlights: &mut Vec<Highlight>,
+) {
+    let mut at = range.start();
+    for line in text[range].split('\n') {
+        let len = TextSize::of(line);
+        let trimmed = TextSize::of(line.trim_end_matches('\r'));
+        if trimmed > 0.into() {
+            highlights.push(Highlight {
+                range: TextRange::at(start + at, trimmed),
+                token_type,
+                modifiers,
+            });
+        }
+        at += len + TextSize::of('\n');
+    }
+}
+
+/// The escape sequences of the text of a basic string, relative to its start. A `\` that
+/// ends a line in a multi-line string is an escape of its own.
+fn escapes(text: &str) -> Vec<TextRange> {
+    let mut escapes = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            continue;
+        }
+        let len = match chars.peek() {
+            Some((_, 'u')) => 6,
+            Some((_, 'U')) => 10,
+            Some((_, c)) if c.is_whitespace() => 1,
+            Some(_) => 2,
+            None => 1,
+        };
+        let end = (i + len).min(text.len());
+        escapes.push(TextRange::new((i as u32).into(), (end as u32).into()));
+        // Escape sequences are ASCII unless they are invalid, so skipping by characters
+        // never goes past a valid one.
+        while chars.peek().is_some_and(|&(j, _)| j < end) {
+            chars.next();
+        }
+    }
+    escapes
+}
+
+/// Encodes highlights, which are sorted and on a single line each, relative to each other.
+fn encode(highlights: &[Highlight], line_index: &LineIndex) -> Vec<SemanticToken> {
+    let mut tokens = Vec::with_capacity(highlights.len());
+    let (mut line, mut character) = (0, 0);
+    for highlight in highlights {
+        let range = line_index.range(highlight.range);
+        let delta_line = range.start.line - line;
+        let delta_start = if delta_line == 0 {
+            range.start.character - character
+        } else {
+            range.start.character
+        };
+        tokens.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length: range.end.character - range.start.character,
+            token_type: highlight.token_type,
+            token_modifiers_bitset: highlight.modifiers,
+        });
+        line = range.start.line;
+        character = range.start.character;
+    }
+    tokens
+}
+
+/// The edits that turn `old` into `new`: a single edit of the part between what they
+/// start and end with, if they differ at all. Edits count in numbers, five to a token.
+pub fn diff(old: &[SemanticToken], new: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+    let suffix = old_rest
+        .iter()
+        .rev()
+        .zip(new_rest.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let deleted = old_rest.len() - suffix;
+    let inserted = &new_rest[..new_rest.len() - suffix];
+    if deleted == 0 && inserted.is_empty() {
+        return Vec::new();
+    }
+    vec![SemanticTokensEdit {
+        start: 5 * prefix as u32,
+        delete_count: 5 * deleted as u32,
+        data: Some(inserted.to_vec()),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::{expect, Expect};
+
+    use super::*;
+    use crate::{line_index::PositionEncoding, parser::parse};
+
+    fn check(input: &str, range: Option<TextRange>, expect: Expect) {
+        let ast = parse(input);
+        let line_index = LineIndex::new(input, PositionEncoding::Utf16);
+        let (mut line, mut character) = (0, 0);
+        let mut out = String::new();
+        for token in semantic_tokens(&ast, &line_index, range) {
+            if token.delta_line > 0 {
+                line += token.delta_line;
+                character = 0;
+            }
+            character += token.delta_start;
+            let modifiers: Vec<_> = MODIFIERS
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| token.token_modifiers_bitset & 1 << i != 0)
+                .map(|(_, name)| *name)
+                .collect();
+            let line = format!(
+                "{}:{} {} {} {}",
+                line,
+                character,
+                token.length,
+                TYPES[token.token_type as usize],
+                modifiers.join(",")
+            );
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        expect.assert_eq(&out);
+    }
+
+    #[test]
+    fn tokens() {
+        check(
+            r#"# é comment
+[server."a.b"]
+ip = "1\té\u00e9"
+ports = [8000, 1.5e3, true]
+[[fruit]]
+born = 1979-05-27T07:32:00Z
+day = 1979-05-27
+lit = '\n'
+text = """
+one \
+  two"""
+"#,
+            None,
+            expect![[r#"
+                0:0 11 comment
+                1:1 6 namespace
+                1:8 5 namespace
+                2:0 2 property
+                2:5 2 string
+                2:7 2 escapeSequence
+                2:9 1 string
+                2:10 6 escapeSequence
+                2:16 1 string
+                3:0 5 property
+                3:9 4 number integer
+                3:15 5 number float
+                3:22 4 boolean
+                4:2 5 struct
+                5:0 4 property
+                5:7 20 dateTime date,time,offset
+                6:0 3 property
+                6:6 10 dateTime date
+                7:0 3 property
+                7:6 4 string literal
+                8:0 4 property
+                8:7 3 string multiline
+                9:0 4 string multiline
+                9:4 1 escapeSequence
+                10:0 8 string multiline
+            "#]],
+        );
+        check(
+            "a = 1\nb = 2\nc = 3\n",
+            Some(TextRange::new(7.into(), 12.into())),
+            expect![[r#"
+                1:4 1 number integer
+            "#]],
+        );
+    }
+
+    #[test]
+    fn deltas() {
+        let tokens = |input: &str| {
+            let line_index = LineIndex::new(input, PositionEncoding::Utf16);
+            semantic_tokens(&parse(input), &line_index, None)
+        };
+        let old = tokens("a = 1\nb = 2\nc = 3\n");
+        let new = tokens("a = 1\nb = 'x'\nc = 3\n");
+        let edits = diff(&old, &new);
+        assert_eq!(edits.len(), 1);
+        assert_eq!((edits[0].start, edits[0].delete_count), (15, 5));
+        assert_eq!(edits[0].data.as_deref(), Some(&new[3..4]));
+        assert_eq!(diff(&new, &new), Vec::new());
+    }
+}
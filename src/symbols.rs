@@ -0,0 +1,257 @@
+//! The outline of a document, for `textDocument/documentSymbol`.
+
+use lsp_types::{DocumentSymbol, Range, SymbolKind};
+
+use crate::{
+    line_index::LineIndex,
+    parser::{
+        ast::{AstNode, Entry, Key, KeyValue, Value},
+        AST,
+    },
+};
+
+/// Returns the headers of `ast` as namespaces and arrays, with the key/value pairs below
+/// them as properties. A header is nested in the closest header before it whose key is a
+/// prefix of its own, and inline tables are nested in the key they are assigned to.
+pub fn document_symbols(ast: &AST, line_index: &LineIndex) -> Vec<DocumentSymbol> {
+    let mut symbols = Vec::new();
+    // The headers that later headers can still be nested in, with their keys.
+    let mut open: Vec<(Vec<String>, DocumentSymbol)> = Vec::new();
+
+    for entry in ast.root().entries() {
+        let (key, kind, entries) = match &entry {
+            Entry::KeyValue(kv) => {
+                symbols.extend(key_value(kv, line_index));
+                continue;
+            }
+            Entry::Table(header) => {
+                let entries: Vec<_> = header.entries().collect();
+                (header.key(), SymbolKind::Namespace, entries)
+            }
+            Entry::ArrayOfTables(header) => {
+                let entries: Vec<_> = header.entries().collect();
+                (header.key(), SymbolKind::Array, entries)
+            }
+        };
+        let key = match key {
+            Some(key) => key,
+            None => continue,
+        };
+        let path = names(&key);
+
+        while let Some((parent, _)) = open.last() {
+            if parent.len() < path.len() && path.starts_with(parent) {
+                break;
+            }
+            close(&mut open, &mut symbols);
+        }
+        let parent_len = open.last().map_or(0, |(parent, _)| parent.len());
+
+        let children = entries
+            .iter()
+            .filter_map(|kv| key_value(kv, line_index))
+            .collect();
+        let symbol = symbol(
+            display_name(&key, parent_len),
+            kind,
+            line_index.range(entry.syntax().text_range()),
+            line_index.range(key.text_range()),
+            children,
+        );
+        open.push((path, symbol));
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut symbols);
+    }
+    symbols
+}
+
+/// Moves the innermost open header into its parent, or to the top level.
+fn close(open: &mut Vec<(Vec<String>, DocumentSymbol)>, symbols: &mut Vec<DocumentSymbol>) {
+    let (_, symbol) = match open.pop() {
+        Some(it) => it,
+        None => return,
+    };
+    match open.last_mut() {
+        Some((_, parent)) => {
+            // The section of a parent ends at the next header, so it has to grow to contain
+            // the sections nested in it.
+            parent.range.end = parent.range.end.max(symbol.range.end);
+            parent.children.get_or_insert_with(Vec::new).push(symbol);
+        }
+        None => symbols.push(symbol),
+    }
+}
+
+fn key_value(kv: &KeyValue, line_index: &LineIndex) -> Option<DocumentSymbol> {
+    let key = kv.key()?;
+    let (detail, children) = match kv.value() {
+        Some(value) => (
+            Some(value.syntax().to_string()).filter(|_| matches!(value, Value::Scalar(_))),
+            value_children(&value, line_index),
+        ),
+        None => (None, Vec::new()),
+    };
+    let mut symbol = symbol(
+        display_name(&key, 0),
+        SymbolKind::Property,
+        line_index.range(kv.text_range()),
+        line_index.range(key.text_range()),
+        children,
+    );
+    symbol.detail = detail;
+    Some(symbol)
+}
+
+/// The symbols nested in a value: the keys of an inline table, and the inline tables and
+/// arrays in an array, named after their index.
+fn value_children(value: &Value, line_index: &LineIndex) -> Vec<DocumentSymbol> {
+    match value {
+        Value::InlineTable(table) => table
+            .entries()
+            .filter_map(|kv| key_value(&kv, line_index))
+            .collect(),
+        Value::Array(array) => array
+            .values()
+            .enumerate()
+            .filter(|(_, value)| !matches!(value, Value::Scalar(_)))
+            .map(|(i, value)| {
+                let kind = match value {
+                    Value::Array(_) => SymbolKind::Array,
+                    _ => SymbolKind::Object,
+                };
+                let range = line_index.range(value.text_range());
+                let children = value_children(&value, line_index);
+                symbol(format!("[{}]", i), kind, range, range, children)
+            })
+            .collect(),
+        Value::Scalar(_) => Vec::new(),
+    }
+}
+
+fn names(key: &Key) -> Vec<String> {
+    key.segments()
+        .map(|segment| segment.name().unwrap_or_default())
+        .collect()
+}
+
+/// The key as written, without the first `skip` segments.
+fn display_name(key: &Key, skip: usize) -> String {
+    let segments: Vec<_> = key
+        .segments()
+        .skip(skip)
+        .map(|segment| segment.syntax().to_string())
+        .collect();
+    segments.join(".")
+}
+
+#[allow(deprecated)]
+fn symbol(
+    name: String,
+    kind: SymbolKind,
+    range: Range,
+    selection_range: Range,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        // Clients reject symbols with empty names, which keys like `""` would have.
+        name: if name.is_empty() { "\"\"".into() } else { name },
+        detail: None,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range,
+        children: Some(children).filter(|it| !it.is_empty()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::{expect, Expect};
+
+    use super::*;
+    use crate::{line_index::PositionEncoding, parser::parse};
+
+    fn check(input: &str, expect: Expect) {
+        fn dump(out: &mut String, symbols: &[DocumentSymbol], depth: usize) {
+            for symbol in symbols {
+                let r = |range: Range| {
+                    format!(
+                        "{}:{}-{}:{}",
+                        range.start.line,
+                        range.start.character,
+                        range.end.line,
+                        range.end.character
+                    )
+                };
+                out.push_str(&format!(
+                    "{}{} {:?} {} @{}",
+                    "  ".repeat(depth),
+                    symbol.name,
+                    symbol.kind,
+                    r(symbol.range),
+                    r(symbol.selection_range),
+                ));
+                if let Some(detail) = &symbol.detail {
+                    out.push_str(&format!(" = {}", detail));
+                }
+                out.push('\n');
+                dump(
+                    out,
+                    symbol.children.as_deref().unwrap_or_default(),
+                    depth + 1,
+                );
+            }
+        }
+
+        let line_index = LineIndex::new(input, PositionEncoding::Utf16);
+        let mut out = String::new();
+        dump(&mut out, &document_symbols(&parse(input), &line_index), 0);
+        expect.assert_eq(&out);
+    }
+
+    #[test]
+    fn outline() {
+        let input = r#"title = "x"
+point = { x = 1, y = { z = 2 } }
+
+[servers]
+list = [1, { a = true }, [{}]]
+
+[servers."alpha.1"]
+ip = "10.0.0.1"
+
+[[fruit]]
+name = "apple"
+
+[[fruit.variety]]
+name = "red"
+
+[other]
+"#;
+        check(
+            input,
+            expect![[r#"
+            title Property 0:0-0:11 @0:0-0:5 = "x"
+            point Property 1:0-1:32 @1:0-1:5
+              x Property 1:10-1:15 @1:10-1:11 = 1
+              y Property 1:17-1:30 @1:17-1:18
+                z Property 1:23-1:28 @1:23-1:24 = 2
+            servers Namespace 3:0-9:0 @3:1-3:8
+              list Property 4:0-4:30 @4:0-4:4
+                [1] Object 4:11-4:23 @4:11-4:23
+                  a Property 4:13-4:21 @4:13-4:14 = true
+                [2] Array 4:25-4:29 @4:25-4:29
+                  [0] Object 4:26-4:28 @4:26-4:28
+              "alpha.1" Namespace 6:0-9:0 @6:1-6:18
+                ip Property 7:0-7:15 @7:0-7:2 = "10.0.0.1"
+            fruit Array 9:0-15:0 @9:2-9:7
+              name Property 10:0-10:14 @10:0-10:4 = "apple"
+              variety Array 12:0-15:0 @12:2-12:15
+                name Property 13:0-13:12 @13:0-13:4 = "red"
+            other Namespace 15:0-16:0 @15:1-15:6
+        "#]],
+        );
+    }
+}